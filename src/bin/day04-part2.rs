@@ -3,22 +3,20 @@ use nom::character::complete::{char, line_ending, space1, u32};
 use nom::multi::separated_list1;
 use nom::sequence::{preceded, separated_pair, tuple};
 use nom::{Finish, Parser};
-use std::cell::RefCell;
-use std::collections::{HashMap, HashSet};
 use std::fs::read_to_string;
 
 #[derive(Debug)]
 struct Card {
-    winning: HashSet<u32>,
-    my: HashSet<u32>,
+    winning: Vec<u32>,
+    my: Vec<u32>,
 }
 
 impl Card {
     fn new(winning: Vec<u32>, yours: Vec<u32>) -> Self {
-        Self {
-            winning: winning.into_iter().collect(),
-            my: yours.into_iter().collect(),
-        }
+        Self { winning, my: yours }
+    }
+    fn matches(&self) -> usize {
+        self.my.iter().filter(|n| self.winning.contains(n)).count()
     }
 }
 
@@ -35,31 +33,141 @@ fn parse(input: &str) -> anyhow::Result<Vec<Card>> {
         .map(|(_, parsed)| parsed)
         .map_err(|e: nom::error::VerboseError<&str>| anyhow::anyhow!("parser error: {:?}", e))
 }
-fn part2(cards: &[Card]) -> usize {
-    let cache = RefCell::new(HashMap::new());
-    fn count_copies(
-        cards_copies: &[usize],
-        id: usize,
-        cache: &RefCell<HashMap<usize, usize>>,
-    ) -> usize {
-        if cache.borrow().contains_key(&id) {
-            return cache.borrow()[&id];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CopyRule {
+    Following,
+    Previous,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scoring {
+    Doubling,
+    Linear,
+    Geometric(u64),
+}
+
+impl Scoring {
+    fn points(self, matches: usize) -> Option<u64> {
+        match (self, matches) {
+            (_, 0) => Some(0),
+            (Scoring::Doubling, m) => Scoring::Geometric(2).points(m),
+            (Scoring::Linear, m) => u64::try_from(m).ok(),
+            (Scoring::Geometric(base), m) => base.checked_pow(u32::try_from(m - 1).ok()?),
         }
-        let count = (1..=cards_copies[id])
-            .fold(1, |acc, i| acc + count_copies(cards_copies, id + i, cache));
-        cache.borrow_mut().insert(id, count);
-        count
     }
-    let cards_copies: Vec<_> = cards
-        .iter()
-        .map(|c| c.my.intersection(&c.winning).count())
-        .collect();
-    (0..cards_copies.len()).fold(0, |acc, id| acc + count_copies(&cards_copies, id, &cache))
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Rules {
+    copies: CopyRule,
+    scoring: Scoring,
+    max_total_copies: Option<usize>,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self {
+            copies: CopyRule::Following,
+            scoring: Scoring::Doubling,
+            max_total_copies: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Scratchcards {
+    matches: Vec<usize>,
+    rules: Rules,
+}
+
+impl Scratchcards {
+    fn new(cards: &[Card], rules: Rules) -> Self {
+        Self {
+            matches: cards.iter().map(Card::matches).collect(),
+            rules,
+        }
+    }
+    fn points(&self) -> anyhow::Result<Vec<u64>> {
+        self.matches
+            .iter()
+            .enumerate()
+            .map(|(id, &m)| {
+                self.rules
+                    .scoring
+                    .points(m)
+                    .ok_or_else(|| anyhow::anyhow!("points for card {} overflow", id + 1))
+            })
+            .collect()
+    }
+    fn copies(&self) -> Vec<usize> {
+        match self.rules.copies {
+            CopyRule::Following => self.cascade(self.matches.iter().copied()),
+            CopyRule::Previous => {
+                let mut copies = self.cascade(self.matches.iter().rev().copied());
+                copies.reverse();
+                copies
+            }
+        }
+    }
+    fn cascade(&self, matches: impl ExactSizeIterator<Item = usize>) -> Vec<usize> {
+        let len = matches.len();
+        let mut diff = vec![0_isize; len + 1];
+        let mut running = 0_isize;
+        let mut budget = self.rules.max_total_copies.unwrap_or(usize::MAX);
+        let mut copies = Vec::with_capacity(len);
+        for (id, won) in matches.enumerate() {
+            running += diff[id];
+            let count = 1 + running as usize;
+            let from = (id + 1).min(len);
+            let won = (id + 1 + won).min(len) - from;
+            let full = won.min(budget / count);
+            let rest = if full < won { budget - full * count } else { 0 };
+            budget -= full * count + rest;
+            diff[from] += count as isize;
+            diff[from + full] -= count as isize;
+            if rest > 0 {
+                diff[from + full] += rest as isize;
+                diff[from + full + 1] -= rest as isize;
+            }
+            copies.push(count);
+        }
+        copies
+    }
+}
+
+fn part2(cards: &[Card]) -> usize {
+    Scratchcards::new(cards, Rules::default())
+        .copies()
+        .into_iter()
+        .sum()
 }
 
 fn main() {
     let input = read_to_string("inputs/day04-input1.txt").unwrap();
     let cards = parse(&input).unwrap();
+    if std::env::args().any(|arg| arg == "--house-rules") {
+        for (copies, scoring) in [
+            (CopyRule::Following, Scoring::Doubling),
+            (CopyRule::Previous, Scoring::Linear),
+            (CopyRule::Following, Scoring::Geometric(3)),
+        ] {
+            let rules = Rules {
+                copies,
+                scoring,
+                ..Rules::default()
+            };
+            let scratchcards = Scratchcards::new(&cards, rules);
+            let total = scratchcards.copies().into_iter().sum::<usize>();
+            match scratchcards.points() {
+                Ok(points) => {
+                    let points = points.into_iter().sum::<u64>();
+                    println!("{copies:?}/{scoring:?}: {points} points, {total} cards");
+                }
+                Err(err) => println!("{copies:?}/{scoring:?}: {err}, {total} cards"),
+            }
+        }
+    }
     let answer = part2(&cards);
     println!("answer is: {answer}");
 }
@@ -80,14 +188,8 @@ Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11
     #[test]
     fn parsing() -> anyhow::Result<()> {
         let cards = parse(INPUT.trim())?;
-        assert_eq!(
-            cards[0].winning,
-            vec![41, 48, 83, 86, 17].into_iter().collect()
-        );
-        assert_eq!(
-            cards[0].my,
-            vec![83, 86, 6, 31, 17, 9, 48, 53].into_iter().collect()
-        );
+        assert_eq!(cards[0].winning, vec![41, 48, 83, 86, 17]);
+        assert_eq!(cards[0].my, vec![83, 86, 6, 31, 17, 9, 48, 53]);
         Ok(())
     }
     #[test]
@@ -96,4 +198,56 @@ Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11
         assert_eq!(part2(&cards), 30);
         Ok(())
     }
+    #[test]
+    fn copies_test() -> anyhow::Result<()> {
+        let cards = parse(INPUT.trim())?;
+        let engine = Scratchcards::new(&cards, Rules::default());
+        assert_eq!(engine.copies(), vec![1, 2, 4, 8, 14, 1]);
+        assert_eq!(engine.points()?, vec![8, 2, 2, 1, 0, 0]);
+        Ok(())
+    }
+    #[test]
+    fn house_rules_test() -> anyhow::Result<()> {
+        let cards = parse(INPUT.trim())?;
+        let rules = Rules {
+            copies: CopyRule::Previous,
+            scoring: Scoring::Linear,
+            max_total_copies: None,
+        };
+        let engine = Scratchcards::new(&cards, rules);
+        assert_eq!(engine.copies(), vec![6, 3, 2, 1, 1, 1]);
+        assert_eq!(engine.points()?, vec![4, 2, 2, 1, 0, 0]);
+        let capped = Scratchcards::new(
+            &cards,
+            Rules {
+                max_total_copies: Some(3),
+                ..rules
+            },
+        );
+        assert_eq!(capped.copies(), vec![1, 3, 2, 1, 1, 1]);
+        let capped = Scratchcards::new(
+            &cards,
+            Rules {
+                max_total_copies: Some(10),
+                ..Rules::default()
+            },
+        );
+        let copies = capped.copies();
+        assert_eq!(copies, vec![1, 2, 4, 6, 2, 1]);
+        assert_eq!(copies.iter().sum::<usize>() - copies.len(), 10);
+        Ok(())
+    }
+    #[test]
+    fn points_overflow() -> anyhow::Result<()> {
+        assert_eq!(Scoring::Geometric(10).points(19), Some(10_u64.pow(18)));
+        assert_eq!(Scoring::Geometric(10).points(25), None);
+        assert_eq!(Scoring::Doubling.points(65), None);
+        let cards = parse("Card 1: 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16 17 18 19 20 21 22 23 24 25 | 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16 17 18 19 20 21 22 23 24 25")?;
+        let rules = Rules {
+            scoring: Scoring::Geometric(10),
+            ..Rules::default()
+        };
+        assert!(Scratchcards::new(&cards, rules).points().is_err());
+        Ok(())
+    }
 }
@@ -1,31 +1,228 @@
-use std::collections::HashMap;
+#![allow(clippy::single_range_in_vec_init)]
+use std::fmt::Display;
 use std::fs::read_to_string;
 use std::ops::Range;
 
+use itertools::Itertools;
 use nom::bytes::complete::tag;
-use nom::character::complete::{line_ending, space1, u64};
+use nom::character::complete::{alpha1, line_ending, space1, u64};
 use nom::combinator::map;
 use nom::multi::separated_list1;
-use nom::sequence::preceded;
+use nom::sequence::{preceded, separated_pair, terminated, tuple};
 use nom::{Finish, IResult, Parser};
+use petgraph::algo::astar;
+use petgraph::graphmap::DiGraphMap;
 
-struct FoodProd {
+#[derive(Debug)]
+struct CategoryMap<'a> {
+    from: &'a str,
+    to: &'a str,
+    ranges: Vec<(Range<u64>, Range<u64>)>,
+}
+impl<'a> CategoryMap<'a> {
+    fn new(from: &'a str, to: &'a str, mut ranges: Vec<(Range<u64>, Range<u64>)>) -> Self {
+        ranges.sort_by_key(|(src, _)| src.start);
+        Self { from, to, ranges }
+    }
+    fn forward(&self, seeds: &[Range<u64>]) -> Vec<Range<u64>> {
+        transfer(seeds, self.ranges.iter().map(|(src, dst)| (src, dst)), self)
+    }
+    fn backward(&self, seeds: &[Range<u64>]) -> Vec<Range<u64>> {
+        transfer(seeds, self.ranges.iter().map(|(src, dst)| (dst, src)), self)
+    }
+    fn unmapped(&self, seed: &Range<u64>) -> Vec<Range<u64>> {
+        let mut holes = vec![];
+        let mut cursor = seed.start;
+        for (src, _) in &self.ranges {
+            if src.start > cursor {
+                holes.push(cursor..src.start.min(seed.end));
+            }
+            cursor = cursor.max(src.end);
+            if cursor >= seed.end {
+                break;
+            }
+        }
+        if cursor < seed.end {
+            holes.push(cursor..seed.end);
+        }
+        holes.retain(|r| !r.is_empty());
+        holes
+    }
+}
+fn transfer<'r>(
+    seeds: &[Range<u64>],
+    pairs: impl Iterator<Item = (&'r Range<u64>, &'r Range<u64>)> + Clone,
+    mapping: &CategoryMap,
+) -> Vec<Range<u64>> {
+    let mut result = vec![];
+    for seed in seeds {
+        for (from, to) in pairs.clone() {
+            let start = seed.start.max(from.start);
+            let end = seed.end.min(from.end);
+            if start < end {
+                result.push(to.start + start - from.start..to.start + end - from.start);
+            }
+        }
+        result.extend(mapping.unmapped(seed));
+    }
+    result
+}
+
+#[derive(Debug)]
+struct Almanac<'a> {
     seeds: Vec<u64>,
-    seed_to_soil: HashMap<Range<u64>, Range<u64>>,
-    soil_to_fertilizer: HashMap<Range<u64>, Range<u64>>,
-    fertilizer_to_water: HashMap<Range<u64>, Range<u64>>,
-    water_to_light: HashMap<Range<u64>, Range<u64>>,
-    light_to_temperature: HashMap<Range<u64>, Range<u64>>,
-    temperature_to_humidity: HashMap<Range<u64>, Range<u64>>,
-    humidity_to_location: HashMap<Range<u64>, Range<u64>>,
+    maps: Vec<CategoryMap<'a>>,
+    categories: DiGraphMap<&'a str, usize>,
+}
+impl<'a> Almanac<'a> {
+    fn new(seeds: Vec<u64>, maps: Vec<CategoryMap<'a>>) -> Self {
+        let categories = maps
+            .iter()
+            .enumerate()
+            .map(|(i, map)| (map.from, map.to, i))
+            .collect();
+        Self {
+            seeds,
+            maps,
+            categories,
+        }
+    }
+    fn route(&self, from: &str, to: &str) -> Option<Vec<usize>> {
+        let (_cost, path) = astar(&self.categories, from, |c| c == to, |_| 1, |_| 0)?;
+        Some(
+            path.iter()
+                .tuple_windows()
+                .map(|(&a, &b)| self.categories[(a, b)])
+                .collect(),
+        )
+    }
+    fn map_ranges(
+        &self,
+        from: &str,
+        to: &str,
+        seeds: &[Range<u64>],
+    ) -> anyhow::Result<Vec<Range<u64>>> {
+        if let Some(route) = self.route(from, to) {
+            Ok(route
+                .into_iter()
+                .fold(seeds.to_vec(), |acc, i| self.maps[i].forward(&acc)))
+        } else if let Some(route) = self.route(to, from) {
+            Ok(route
+                .into_iter()
+                .rev()
+                .fold(seeds.to_vec(), |acc, i| self.maps[i].backward(&acc)))
+        } else {
+            Err(anyhow::anyhow!("no mapping between {from} and {to}"))
+        }
+    }
+    fn compose(&self, from: &str, to: &str) -> anyhow::Result<Piecewise> {
+        let route = self
+            .route(from, to)
+            .ok_or_else(|| anyhow::anyhow!("no mapping from {from} to {to}"))?;
+        Ok(route.into_iter().fold(Piecewise::identity(), |acc, i| {
+            acc.then(&Piecewise::from(&self.maps[i]))
+        }))
+    }
+    fn map_value(&self, from: &str, to: &str, value: u64) -> anyhow::Result<Option<u64>> {
+        let ranges = self.map_ranges(from, to, &[value..value + 1])?;
+        Ok(ranges.into_iter().map(|range| range.start).min())
+    }
 }
-fn parse(input: &str) -> anyhow::Result<FoodProd> {
-    let (_rest, food_prod) = parse_input(input)
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Piecewise {
+    pieces: Vec<(u64, i64)>,
+}
+impl Piecewise {
+    fn identity() -> Self {
+        Self {
+            pieces: vec![(0, 0)],
+        }
+    }
+    fn push(&mut self, start: u64, offset: i64) {
+        if let Some(last) = self.pieces.last_mut().filter(|(s, _)| *s == start) {
+            last.1 = offset;
+            let len = self.pieces.len();
+            if len > 1 && self.pieces[len - 2].1 == offset {
+                self.pieces.pop();
+            }
+        } else if self.pieces.last().is_none_or(|&(_, o)| o != offset) {
+            self.pieces.push((start, offset));
+        }
+    }
+    fn breakpoints(&self) -> impl Iterator<Item = u64> + '_ {
+        self.pieces.iter().map(|&(start, _)| start)
+    }
+    fn segments(&self) -> impl Iterator<Item = (Range<u64>, i64)> + '_ {
+        self.pieces.iter().enumerate().map(|(i, &(start, offset))| {
+            let end = self.pieces.get(i + 1).map_or(u64::MAX, |&(next, _)| next);
+            (start..end, offset)
+        })
+    }
+    fn segments_from(&self, value: u64) -> impl Iterator<Item = (Range<u64>, i64)> + '_ {
+        let first = self.pieces.partition_point(|&(start, _)| start <= value) - 1;
+        self.segments().skip(first)
+    }
+    fn apply(&self, value: u64) -> u64 {
+        let (_, offset) = self.segments_from(value).next().unwrap();
+        value.wrapping_add_signed(offset)
+    }
+    fn then(&self, next: &Piecewise) -> Piecewise {
+        let mut composed = Piecewise { pieces: vec![] };
+        for (range, offset) in self.segments() {
+            let lo = range.start.wrapping_add_signed(offset);
+            let hi = range.end.wrapping_add_signed(offset);
+            for (image, next_offset) in next.segments_from(lo).take_while(|(r, _)| r.start < hi) {
+                let start = image.start.max(lo).wrapping_add_signed(-offset);
+                composed.push(start, offset + next_offset);
+            }
+        }
+        composed
+    }
+    fn min_over(&self, ranges: &[Range<u64>]) -> Option<u64> {
+        ranges
+            .iter()
+            .filter(|range| !range.is_empty())
+            .flat_map(|range| {
+                let inner = self.breakpoints().filter(|start| range.contains(start));
+                std::iter::once(range.start).chain(inner)
+            })
+            .map(|value| self.apply(value))
+            .min()
+    }
+}
+impl From<&CategoryMap<'_>> for Piecewise {
+    fn from(map: &CategoryMap<'_>) -> Self {
+        let mut piecewise = Piecewise::identity();
+        for (src, dst) in &map.ranges {
+            piecewise.push(src.start, dst.start as i64 - src.start as i64);
+            piecewise.push(src.end, 0);
+        }
+        piecewise
+    }
+}
+impl Display for Piecewise {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (range, offset) in self.segments() {
+            let start = range.start.wrapping_add_signed(offset);
+            if range.end == u64::MAX {
+                writeln!(f, "{}.. -> {start}..", range.start)?;
+            } else {
+                let end = range.end.wrapping_add_signed(offset);
+                writeln!(f, "{}..{} -> {start}..{end}", range.start, range.end)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse(input: &str) -> anyhow::Result<Almanac<'_>> {
+    let (_rest, almanac) = parse_input(input)
         .finish()
         .map_err(|e| anyhow::anyhow!("parser error: {:?}", e))?;
-    Ok(food_prod)
+    Ok(almanac)
 }
-fn parse_input(input: &str) -> IResult<&str, FoodProd> {
+fn parse_input(input: &str) -> IResult<&str, Almanac<'_>> {
     let (i, seeds) = preceded(tag("seeds: "), separated_list1(space1, u64))(input)?;
     let numbers = |i| {
         map(separated_list1(space1, u64), |v| {
@@ -34,75 +231,44 @@ fn parse_input(input: &str) -> IResult<&str, FoodProd> {
     };
     let mappings = |i| separated_list1(line_ending, numbers)(i);
     let gap = |i| line_ending.and(line_ending).parse(i);
-
-    let (i, seed_to_soil) =
-        preceded(gap.and(tag("seed-to-soil map:")).and(line_ending), mappings)(i)?;
-    let (i, soil_to_fertilizer) = preceded(
-        gap.and(tag("soil-to-fertilizer map:")).and(line_ending),
-        mappings,
-    )(i)?;
-    let (i, fertilizer_to_water) = preceded(
-        gap.and(tag("fertilizer-to-water map:")).and(line_ending),
-        mappings,
-    )(i)?;
-    let (i, water_to_light) = preceded(
-        gap.and(tag("water-to-light map:")).and(line_ending),
-        mappings,
-    )(i)?;
-    let (i, light_to_temperature) = preceded(
-        gap.and(tag("light-to-temperature map:")).and(line_ending),
-        mappings,
-    )(i)?;
-    let (i, temperature_to_humidity) = preceded(
-        gap.and(tag("temperature-to-humidity map:"))
-            .and(line_ending),
-        mappings,
-    )(i)?;
-    let (i, humidity_to_location) = preceded(
-        gap.and(tag("humidity-to-location map:")).and(line_ending),
-        mappings,
-    )(i)?;
-    Ok((
-        i,
-        FoodProd {
-            seeds,
-            seed_to_soil: seed_to_soil.into_iter().collect(),
-            soil_to_fertilizer: soil_to_fertilizer.into_iter().collect(),
-            fertilizer_to_water: fertilizer_to_water.into_iter().collect(),
-            water_to_light: water_to_light.into_iter().collect(),
-            light_to_temperature: light_to_temperature.into_iter().collect(),
-            temperature_to_humidity: temperature_to_humidity.into_iter().collect(),
-            humidity_to_location: humidity_to_location.into_iter().collect(),
-        },
-    ))
-}
-fn location(seed: u64, mapping: &HashMap<Range<u64>, Range<u64>>) -> u64 {
-    mapping
-        .keys()
-        .find(|src| src.contains(&seed))
-        .map(|src| mapping[src].start + seed - src.start)
-        .unwrap_or(seed)
+    let header = terminated(
+        separated_pair(alpha1, tag("-to-"), alpha1),
+        tuple((tag(" map:"), line_ending)),
+    );
+    let section = header
+        .and(mappings)
+        .map(|((from, to), ranges)| CategoryMap::new(from, to, ranges));
+    let (i, maps) = preceded(gap, separated_list1(gap, section))(i)?;
+    Ok((i, Almanac::new(seeds, maps)))
 }
-fn part1(food_prod: &FoodProd) -> u64 {
-    food_prod
+
+fn part1(almanac: &Almanac) -> anyhow::Result<u64> {
+    let seeds = almanac
         .seeds
         .iter()
-        .copied()
-        .map(|seed| location(seed, &food_prod.seed_to_soil))
-        .map(|soil| location(soil, &food_prod.soil_to_fertilizer))
-        .map(|fert| location(fert, &food_prod.fertilizer_to_water))
-        .map(|wate| location(wate, &food_prod.water_to_light))
-        .map(|ligh| location(ligh, &food_prod.light_to_temperature))
-        .map(|temp| location(temp, &food_prod.temperature_to_humidity))
-        .map(|humi| location(humi, &food_prod.humidity_to_location))
-        .min()
-        .unwrap()
+        .map(|&seed| seed..seed + 1)
+        .collect_vec();
+    almanac
+        .compose("seed", "location")?
+        .min_over(&seeds)
+        .ok_or_else(|| anyhow::anyhow!("no seeds"))
 }
 
 fn main() {
     let input = read_to_string("inputs/day05-input1.txt").unwrap();
-    let food_prod = parse(&input).unwrap();
-    let answer = part1(&food_prod);
+    let almanac = parse(&input).unwrap();
+    let trace = std::env::args().any(|arg| arg == "--trace");
+    if trace {
+        for &seed in &almanac.seeds {
+            let location = almanac.map_value("seed", "location", seed).unwrap();
+            println!("seed {seed} -> location {location:?}");
+        }
+    }
+    let answer = part1(&almanac).unwrap();
+    if trace {
+        let seed = almanac.map_value("location", "seed", answer).unwrap();
+        println!("location {answer} comes from seed {seed:?}");
+    }
     println!("answer is: {answer}");
 }
 #[cfg(test)]
@@ -148,25 +314,41 @@ humidity-to-location map:
 "#;
     #[test]
     fn parsing() -> anyhow::Result<()> {
-        let food_production = parse(INPUT.trim())?;
-        assert_eq!(food_production.seeds, vec![79, 14, 55, 13]);
-        assert!(food_production.seed_to_soil.contains_key(&(98..100)));
-        assert!(food_production.seed_to_soil.contains_key(&(50..98)));
-        assert_eq!(food_production.seed_to_soil[&(50..98)], 52..100);
+        let almanac = parse(INPUT.trim())?;
+        assert_eq!(almanac.seeds, vec![79, 14, 55, 13]);
+        assert_eq!((almanac.maps[0].from, almanac.maps[0].to), ("seed", "soil"));
+        assert_eq!(
+            almanac.maps[0].ranges,
+            vec![(50..98, 52..100), (98..100, 50..52)]
+        );
         Ok(())
     }
     #[test]
-    fn location_test() {
-        let map = HashMap::from([(98..100, 50..52), (50..98, 52..100)]);
-        assert_eq!(location(79, &map), 81);
-        assert_eq!(location(14, &map), 14);
-        assert_eq!(location(55, &map), 57);
-        assert_eq!(location(13, &map), 13);
+    fn location_test() -> anyhow::Result<()> {
+        let almanac = parse(INPUT.trim())?;
+        assert_eq!(almanac.map_value("seed", "soil", 79)?, Some(81));
+        assert_eq!(almanac.map_value("seed", "soil", 14)?, Some(14));
+        assert_eq!(almanac.map_value("seed", "soil", 55)?, Some(57));
+        assert_eq!(almanac.map_value("seed", "soil", 13)?, Some(13));
+        assert_eq!(almanac.map_value("location", "seed", 35)?, Some(13));
+        Ok(())
+    }
+    #[test]
+    fn compose_test() -> anyhow::Result<()> {
+        let almanac = parse(INPUT.trim())?;
+        let seed_to_location = almanac.compose("seed", "location")?;
+        for seed in 0..120 {
+            assert_eq!(
+                Some(seed_to_location.apply(seed)),
+                almanac.map_value("seed", "location", seed)?
+            );
+        }
+        Ok(())
     }
     #[test]
     fn part1_test() -> anyhow::Result<()> {
-        let food_prod = parse(INPUT.trim())?;
-        assert_eq!(part1(&food_prod), 35);
+        let almanac = parse(INPUT.trim())?;
+        assert_eq!(part1(&almanac)?, 35);
         Ok(())
     }
 }
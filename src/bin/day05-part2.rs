@@ -1,39 +1,236 @@
 #![allow(clippy::single_range_in_vec_init)]
-use std::collections::HashMap;
+use std::fmt::Display;
 use std::fs::read_to_string;
 use std::ops::Range;
 
+use itertools::Itertools;
 use nom::bytes::complete::tag;
-use nom::character::complete::{line_ending, space1, u64};
+use nom::character::complete::{alpha1, line_ending, space1, u64};
 use nom::combinator::map;
 use nom::multi::separated_list1;
-use nom::sequence::{preceded, separated_pair};
+use nom::sequence::{preceded, separated_pair, terminated, tuple};
 use nom::{Finish, IResult, Parser};
+use petgraph::algo::astar;
+use petgraph::graphmap::DiGraphMap;
 
-struct FoodProd {
-    seeds: Vec<Range<u64>>,
-    seed_to_soil: HashMap<Range<u64>, Range<u64>>,
-    soil_to_fertilizer: HashMap<Range<u64>, Range<u64>>,
-    fertilizer_to_water: HashMap<Range<u64>, Range<u64>>,
-    water_to_light: HashMap<Range<u64>, Range<u64>>,
-    light_to_temperature: HashMap<Range<u64>, Range<u64>>,
-    temperature_to_humidity: HashMap<Range<u64>, Range<u64>>,
-    humidity_to_location: HashMap<Range<u64>, Range<u64>>,
+#[derive(Debug)]
+struct CategoryMap<'a> {
+    from: &'a str,
+    to: &'a str,
+    ranges: Vec<(Range<u64>, Range<u64>)>,
 }
-fn parse(input: &str) -> anyhow::Result<FoodProd> {
-    let (_rest, food_prod) = parse_input(input)
+impl<'a> CategoryMap<'a> {
+    fn new(from: &'a str, to: &'a str, mut ranges: Vec<(Range<u64>, Range<u64>)>) -> Self {
+        ranges.sort_by_key(|(src, _)| src.start);
+        Self { from, to, ranges }
+    }
+    fn forward(&self, seeds: &[Range<u64>]) -> Vec<Range<u64>> {
+        transfer(seeds, self.ranges.iter().map(|(src, dst)| (src, dst)), self)
+    }
+    fn backward(&self, seeds: &[Range<u64>]) -> Vec<Range<u64>> {
+        transfer(seeds, self.ranges.iter().map(|(src, dst)| (dst, src)), self)
+    }
+    fn unmapped(&self, seed: &Range<u64>) -> Vec<Range<u64>> {
+        let mut holes = vec![];
+        let mut cursor = seed.start;
+        for (src, _) in &self.ranges {
+            if src.start > cursor {
+                holes.push(cursor..src.start.min(seed.end));
+            }
+            cursor = cursor.max(src.end);
+            if cursor >= seed.end {
+                break;
+            }
+        }
+        if cursor < seed.end {
+            holes.push(cursor..seed.end);
+        }
+        holes.retain(|r| !r.is_empty());
+        holes
+    }
+}
+fn transfer<'r>(
+    seeds: &[Range<u64>],
+    pairs: impl Iterator<Item = (&'r Range<u64>, &'r Range<u64>)> + Clone,
+    mapping: &CategoryMap,
+) -> Vec<Range<u64>> {
+    let mut result = vec![];
+    for seed in seeds {
+        for (from, to) in pairs.clone() {
+            let start = seed.start.max(from.start);
+            let end = seed.end.min(from.end);
+            if start < end {
+                result.push(to.start + start - from.start..to.start + end - from.start);
+            }
+        }
+        result.extend(mapping.unmapped(seed));
+    }
+    result
+}
+
+#[derive(Debug)]
+struct Almanac<'a> {
+    seeds: Vec<u64>,
+    maps: Vec<CategoryMap<'a>>,
+    categories: DiGraphMap<&'a str, usize>,
+}
+impl<'a> Almanac<'a> {
+    fn new(seeds: Vec<u64>, maps: Vec<CategoryMap<'a>>) -> Self {
+        let categories = maps
+            .iter()
+            .enumerate()
+            .map(|(i, map)| (map.from, map.to, i))
+            .collect();
+        Self {
+            seeds,
+            maps,
+            categories,
+        }
+    }
+    fn seed_ranges(&self) -> Vec<Range<u64>> {
+        self.seeds
+            .iter()
+            .tuples()
+            .map(|(&start, &len)| start..start + len)
+            .collect()
+    }
+    fn route(&self, from: &str, to: &str) -> Option<Vec<usize>> {
+        let (_cost, path) = astar(&self.categories, from, |c| c == to, |_| 1, |_| 0)?;
+        Some(
+            path.iter()
+                .tuple_windows()
+                .map(|(&a, &b)| self.categories[(a, b)])
+                .collect(),
+        )
+    }
+    fn map_ranges(
+        &self,
+        from: &str,
+        to: &str,
+        seeds: &[Range<u64>],
+    ) -> anyhow::Result<Vec<Range<u64>>> {
+        if let Some(route) = self.route(from, to) {
+            Ok(route
+                .into_iter()
+                .fold(seeds.to_vec(), |acc, i| self.maps[i].forward(&acc)))
+        } else if let Some(route) = self.route(to, from) {
+            Ok(route
+                .into_iter()
+                .rev()
+                .fold(seeds.to_vec(), |acc, i| self.maps[i].backward(&acc)))
+        } else {
+            Err(anyhow::anyhow!("no mapping between {from} and {to}"))
+        }
+    }
+    fn compose(&self, from: &str, to: &str) -> anyhow::Result<Piecewise> {
+        let route = self
+            .route(from, to)
+            .ok_or_else(|| anyhow::anyhow!("no mapping from {from} to {to}"))?;
+        Ok(route.into_iter().fold(Piecewise::identity(), |acc, i| {
+            acc.then(&Piecewise::from(&self.maps[i]))
+        }))
+    }
+    fn map_value(&self, from: &str, to: &str, value: u64) -> anyhow::Result<Option<u64>> {
+        let ranges = self.map_ranges(from, to, &[value..value + 1])?;
+        Ok(ranges.into_iter().map(|range| range.start).min())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Piecewise {
+    pieces: Vec<(u64, i64)>,
+}
+impl Piecewise {
+    fn identity() -> Self {
+        Self {
+            pieces: vec![(0, 0)],
+        }
+    }
+    fn push(&mut self, start: u64, offset: i64) {
+        if let Some(last) = self.pieces.last_mut().filter(|(s, _)| *s == start) {
+            last.1 = offset;
+            let len = self.pieces.len();
+            if len > 1 && self.pieces[len - 2].1 == offset {
+                self.pieces.pop();
+            }
+        } else if self.pieces.last().is_none_or(|&(_, o)| o != offset) {
+            self.pieces.push((start, offset));
+        }
+    }
+    fn breakpoints(&self) -> impl Iterator<Item = u64> + '_ {
+        self.pieces.iter().map(|&(start, _)| start)
+    }
+    fn segments(&self) -> impl Iterator<Item = (Range<u64>, i64)> + '_ {
+        self.pieces.iter().enumerate().map(|(i, &(start, offset))| {
+            let end = self.pieces.get(i + 1).map_or(u64::MAX, |&(next, _)| next);
+            (start..end, offset)
+        })
+    }
+    fn segments_from(&self, value: u64) -> impl Iterator<Item = (Range<u64>, i64)> + '_ {
+        let first = self.pieces.partition_point(|&(start, _)| start <= value) - 1;
+        self.segments().skip(first)
+    }
+    fn apply(&self, value: u64) -> u64 {
+        let (_, offset) = self.segments_from(value).next().unwrap();
+        value.wrapping_add_signed(offset)
+    }
+    fn then(&self, next: &Piecewise) -> Piecewise {
+        let mut composed = Piecewise { pieces: vec![] };
+        for (range, offset) in self.segments() {
+            let lo = range.start.wrapping_add_signed(offset);
+            let hi = range.end.wrapping_add_signed(offset);
+            for (image, next_offset) in next.segments_from(lo).take_while(|(r, _)| r.start < hi) {
+                let start = image.start.max(lo).wrapping_add_signed(-offset);
+                composed.push(start, offset + next_offset);
+            }
+        }
+        composed
+    }
+    fn min_over(&self, ranges: &[Range<u64>]) -> Option<u64> {
+        ranges
+            .iter()
+            .filter(|range| !range.is_empty())
+            .flat_map(|range| {
+                let inner = self.breakpoints().filter(|start| range.contains(start));
+                std::iter::once(range.start).chain(inner)
+            })
+            .map(|value| self.apply(value))
+            .min()
+    }
+}
+impl From<&CategoryMap<'_>> for Piecewise {
+    fn from(map: &CategoryMap<'_>) -> Self {
+        let mut piecewise = Piecewise::identity();
+        for (src, dst) in &map.ranges {
+            piecewise.push(src.start, dst.start as i64 - src.start as i64);
+            piecewise.push(src.end, 0);
+        }
+        piecewise
+    }
+}
+impl Display for Piecewise {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (range, offset) in self.segments() {
+            let start = range.start.wrapping_add_signed(offset);
+            if range.end == u64::MAX {
+                writeln!(f, "{}.. -> {start}..", range.start)?;
+            } else {
+                let end = range.end.wrapping_add_signed(offset);
+                writeln!(f, "{}..{} -> {start}..{end}", range.start, range.end)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse(input: &str) -> anyhow::Result<Almanac<'_>> {
+    let (_rest, almanac) = parse_input(input)
         .finish()
         .map_err(|e| anyhow::anyhow!("parser error: {:?}", e))?;
-    Ok(food_prod)
+    Ok(almanac)
 }
-fn parse_input(input: &str) -> IResult<&str, FoodProd> {
-    let (i, seeds) = preceded(
-        tag("seeds: "),
-        separated_list1(
-            space1,
-            separated_pair(u64, space1, u64).map(|(start, len)| start..start + len),
-        ),
-    )(input)?;
+fn parse_input(input: &str) -> IResult<&str, Almanac<'_>> {
+    let (i, seeds) = preceded(tag("seeds: "), separated_list1(space1, u64))(input)?;
     let numbers = |i| {
         map(separated_list1(space1, u64), |v| {
             (v[1]..v[1] + v[2], v[0]..v[0] + v[2])
@@ -41,101 +238,42 @@ fn parse_input(input: &str) -> IResult<&str, FoodProd> {
     };
     let mappings = |i| separated_list1(line_ending, numbers)(i);
     let gap = |i| line_ending.and(line_ending).parse(i);
-
-    let (i, seed_to_soil) =
-        preceded(gap.and(tag("seed-to-soil map:")).and(line_ending), mappings)(i)?;
-    let (i, soil_to_fertilizer) = preceded(
-        gap.and(tag("soil-to-fertilizer map:")).and(line_ending),
-        mappings,
-    )(i)?;
-    let (i, fertilizer_to_water) = preceded(
-        gap.and(tag("fertilizer-to-water map:")).and(line_ending),
-        mappings,
-    )(i)?;
-    let (i, water_to_light) = preceded(
-        gap.and(tag("water-to-light map:")).and(line_ending),
-        mappings,
-    )(i)?;
-    let (i, light_to_temperature) = preceded(
-        gap.and(tag("light-to-temperature map:")).and(line_ending),
-        mappings,
-    )(i)?;
-    let (i, temperature_to_humidity) = preceded(
-        gap.and(tag("temperature-to-humidity map:"))
-            .and(line_ending),
-        mappings,
-    )(i)?;
-    let (i, humidity_to_location) = preceded(
-        gap.and(tag("humidity-to-location map:")).and(line_ending),
-        mappings,
-    )(i)?;
-    Ok((
-        i,
-        FoodProd {
-            seeds,
-            seed_to_soil: seed_to_soil.into_iter().collect(),
-            soil_to_fertilizer: soil_to_fertilizer.into_iter().collect(),
-            fertilizer_to_water: fertilizer_to_water.into_iter().collect(),
-            water_to_light: water_to_light.into_iter().collect(),
-            light_to_temperature: light_to_temperature.into_iter().collect(),
-            temperature_to_humidity: temperature_to_humidity.into_iter().collect(),
-            humidity_to_location: humidity_to_location.into_iter().collect(),
-        },
-    ))
+    let header = terminated(
+        separated_pair(alpha1, tag("-to-"), alpha1),
+        tuple((tag(" map:"), line_ending)),
+    );
+    let section = header
+        .and(mappings)
+        .map(|((from, to), ranges)| CategoryMap::new(from, to, ranges));
+    let (i, maps) = preceded(gap, separated_list1(gap, section))(i)?;
+    Ok((i, Almanac::new(seeds, maps)))
 }
-fn location(seeds: &[Range<u64>], mapping: &HashMap<Range<u64>, Range<u64>>) -> Vec<Range<u64>> {
-    let mut result = vec![];
-    for seed in seeds {
-        let mut seed_map = vec![seed.clone()];
-        for src in mapping.keys() {
-            let mut tmp = vec![];
-            while let Some(seed) = seed_map.pop() {
-                //inside
-                if src.start <= seed.start && seed.end <= src.end {
-                    result.push(
-                        mapping[src].start + seed.start - src.start
-                            ..mapping[src].start + seed.end - src.start,
-                    )
-                //contains
-                } else if seed.start < src.start && src.end < seed.end {
-                    tmp.push(seed.start..src.start);
-                    result.push(mapping[src].start..mapping[src].end);
-                    tmp.push(src.end..seed.end)
-                //left
-                } else if seed.start < src.start && src.start < seed.end && seed.end < src.end {
-                    tmp.push(seed.start..src.start);
-                    result.push(mapping[src].start..mapping[src].start + seed.end - src.start);
-                //right
-                } else if src.start < seed.start && seed.start < src.end && src.end < seed.end {
-                    result.push(mapping[src].start + seed.start - src.start..mapping[src].end);
-                    tmp.push(src.end..seed.end);
-                //outside
-                } else {
-                    tmp.push(seed);
-                }
-            }
-            seed_map = tmp.clone();
-        }
-        result.extend(seed_map);
-    }
-    result
-}
-fn part2(food_prod: &FoodProd) -> u64 {
-    let mut loc = food_prod.seeds.clone();
-    loc = location(&loc, &food_prod.seed_to_soil);
-    loc = location(&loc, &food_prod.soil_to_fertilizer);
-    loc = location(&loc, &food_prod.fertilizer_to_water);
-    loc = location(&loc, &food_prod.water_to_light);
-    loc = location(&loc, &food_prod.light_to_temperature);
-    loc = location(&loc, &food_prod.temperature_to_humidity);
-    loc = location(&loc, &food_prod.humidity_to_location);
-    loc.into_iter().map(|range| range.start).min().unwrap()
+
+fn part2(almanac: &Almanac) -> anyhow::Result<u64> {
+    almanac
+        .compose("seed", "location")?
+        .min_over(&almanac.seed_ranges())
+        .ok_or_else(|| anyhow::anyhow!("no seeds"))
 }
 
 fn main() {
     let input = read_to_string("inputs/day05-input1.txt").unwrap();
-    let food_prod = parse(&input).unwrap();
-    let answer = part2(&food_prod);
+    let almanac = parse(&input).unwrap();
+    let trace = std::env::args().any(|arg| arg == "--trace");
+    if trace {
+        let seeds = almanac.seed_ranges();
+        let locations = almanac.map_ranges("seed", "location", &seeds).unwrap();
+        println!(
+            "{} seed ranges land in {} location ranges",
+            seeds.len(),
+            locations.len()
+        );
+    }
+    let answer = part2(&almanac).unwrap();
+    if trace {
+        let seed = almanac.map_value("location", "seed", answer).unwrap();
+        println!("location {answer} comes from seed {seed:?}");
+    }
     println!("answer is: {answer}");
 }
 #[cfg(test)]
@@ -179,58 +317,107 @@ humidity-to-location map:
 60 56 37
 56 93 4
 "#;
+    fn sorted(mut ranges: Vec<Range<u64>>) -> Vec<Range<u64>> {
+        ranges.sort_by_key(|range| range.start);
+        ranges
+    }
     #[test]
     fn parsing() -> anyhow::Result<()> {
-        let food_production = parse(INPUT.trim())?;
-        assert_eq!(food_production.seeds, vec![79..93, 55..68]);
-        assert!(food_production.seed_to_soil.contains_key(&(98..100)));
-        assert!(food_production.seed_to_soil.contains_key(&(50..98)));
-        assert_eq!(food_production.seed_to_soil[&(50..98)], 52..100);
+        let almanac = parse(INPUT.trim())?;
+        assert_eq!(almanac.seed_ranges(), vec![79..93, 55..68]);
+        assert_eq!(almanac.maps.len(), 7);
+        assert_eq!((almanac.maps[0].from, almanac.maps[0].to), ("seed", "soil"));
+        assert_eq!(
+            almanac.maps[0].ranges,
+            vec![(50..98, 52..100), (98..100, 50..52)]
+        );
         Ok(())
     }
     #[test]
     fn location_inside() {
-        let map = HashMap::from([(18..25, 88..95), (25..95, 18..88)]);
-        let seeds = vec![81..95];
-        assert_eq!(location(&seeds, &map), vec![74..88]);
+        let map = CategoryMap::new("a", "b", vec![(18..25, 88..95), (25..95, 18..88)]);
+        assert_eq!(map.forward(&[81..95]), vec![74..88]);
     }
     #[test]
     fn location_outside() {
-        let map = HashMap::from([(18..25, 88..95), (25..95, 18..88)]);
-        let seeds = vec![1..15];
-        assert_eq!(location(&seeds, &map), vec![1..15]);
+        let map = CategoryMap::new("a", "b", vec![(18..25, 88..95), (25..95, 18..88)]);
+        assert_eq!(map.forward(&[1..15]), vec![1..15]);
     }
     #[test]
     fn location_contains() {
-        let map = HashMap::from([(10..20, 100..120), (40..60, 140..160)]);
-        let seeds = vec![30..70];
-        assert_eq!(location(&seeds, &map), vec![140..160, 60..70, 30..40]);
+        let map = CategoryMap::new("a", "b", vec![(10..20, 100..110), (40..60, 140..160)]);
+        assert_eq!(
+            sorted(map.forward(&[30..70])),
+            vec![30..40, 60..70, 140..160]
+        );
     }
     #[test]
     fn location_left() {
-        let map = HashMap::from([(10..20, 100..120), (40..60, 140..160)]);
-        let seeds = vec![30..50];
-        assert_eq!(location(&seeds, &map), vec![140..150, 30..40]);
+        let map = CategoryMap::new("a", "b", vec![(10..20, 100..110), (40..60, 140..160)]);
+        assert_eq!(sorted(map.forward(&[30..50])), vec![30..40, 140..150]);
     }
     #[test]
     fn location_right() {
-        let map = HashMap::from([(10..20, 100..120), (40..60, 140..160)]);
-        let seeds = vec![50..70];
-        assert_eq!(location(&seeds, &map), vec![150..160, 60..70]);
+        let map = CategoryMap::new("a", "b", vec![(10..20, 100..110), (40..60, 140..160)]);
+        assert_eq!(sorted(map.forward(&[50..70])), vec![60..70, 150..160]);
+    }
+    #[test]
+    fn location_boundaries() {
+        let map = CategoryMap::new("a", "b", vec![(10..20, 100..110), (20..30, 200..210)]);
+        assert_eq!(sorted(map.forward(&[10..25])), vec![100..110, 200..205]);
     }
     #[test]
     fn location_multimap() {
-        let map = HashMap::from([(5..20, 105..120), (30..40, 130..140), (50..70, 150..170)]);
-        let seeds = vec![10..60];
-        let mut new_loc = location(&seeds, &map);
-        new_loc.sort_by(|a, b| a.start.cmp(&b.start));
-
-        assert_eq!(new_loc, vec![20..30, 40..50, 110..120, 130..140, 150..160]);
+        let map = CategoryMap::new(
+            "a",
+            "b",
+            vec![(5..20, 105..120), (30..40, 130..140), (50..70, 150..170)],
+        );
+        assert_eq!(
+            sorted(map.forward(&[10..60])),
+            vec![20..30, 40..50, 110..120, 130..140, 150..160]
+        );
+    }
+    #[test]
+    fn inverse_lookup() -> anyhow::Result<()> {
+        let almanac = parse(INPUT.trim())?;
+        assert_eq!(almanac.map_value("seed", "location", 82)?, Some(46));
+        assert_eq!(almanac.map_value("location", "seed", 46)?, Some(82));
+        assert_eq!(almanac.map_value("soil", "humidity", 84)?, Some(46));
+        assert!(almanac.map_value("seed", "colour", 82).is_err());
+        Ok(())
+    }
+    #[test]
+    fn piecewise_test() {
+        let map = CategoryMap::new("a", "b", vec![(98..100, 50..52), (50..98, 52..100)]);
+        let soil = Piecewise::from(&map);
+        assert_eq!(soil.breakpoints().collect_vec(), vec![0, 50, 98, 100]);
+        assert_eq!(
+            soil.to_string(),
+            "0..50 -> 0..50\n50..98 -> 52..100\n98..100 -> 50..52\n100.. -> 100..\n"
+        );
+        assert_eq!(soil.then(&Piecewise::identity()), soil);
+        let swap = CategoryMap::new("b", "c", vec![(50..52, 98..100), (52..100, 50..98)]);
+        assert_eq!(soil.then(&Piecewise::from(&swap)), Piecewise::identity());
+    }
+    #[test]
+    fn compose_test() -> anyhow::Result<()> {
+        let almanac = parse(INPUT.trim())?;
+        let seed_to_location = almanac.compose("seed", "location")?;
+        for seed in 0..120 {
+            assert_eq!(
+                Some(seed_to_location.apply(seed)),
+                almanac.map_value("seed", "location", seed)?
+            );
+        }
+        assert_eq!(seed_to_location.min_over(&[79..93]), Some(46));
+        assert_eq!(seed_to_location.min_over(&[55..68]), Some(56));
+        Ok(())
     }
     #[test]
     fn part2_test() -> anyhow::Result<()> {
-        let food_prod = parse(INPUT.trim())?;
-        assert_eq!(part2(&food_prod), 46);
+        let almanac = parse(INPUT.trim())?;
+        assert_eq!(part2(&almanac)?, 46);
         Ok(())
     }
 }
@@ -1,54 +1,145 @@
 use std::fs::read_to_string;
+use std::ops::RangeInclusive;
+
+use num::integer::Roots;
+use num::{BigUint, CheckedMul, Integer};
+
+#[derive(Debug, PartialEq, Eq)]
+struct Race<T> {
+    time: T,
+    distance: T,
+}
 
 #[derive(Debug, PartialEq, Eq)]
-struct Race {
-    time: u32,
-    distance: u32,
+enum Outcome<T> {
+    Wins(RangeInclusive<T>),
+    Tie(RangeInclusive<T>),
+    Never,
 }
 
-impl Race {
-    fn new(time: u32, distance: u32) -> Self {
+impl<T> Race<T>
+where
+    T: Clone + Integer + Roots + From<u8> + CheckedMul + Into<BigUint> + TryFrom<BigUint>,
+{
+    fn new(time: T, distance: T) -> Self {
         Self { time, distance }
     }
+    fn beats(&self, hold: &T) -> bool {
+        hold.clone() * (self.time.clone() - hold.clone()) > self.distance
+    }
+    fn outcome(&self) -> Outcome<T> {
+        let two = T::from(2);
+        let (Some(squared), Some(record)) = (
+            self.time.checked_mul(&self.time),
+            self.distance.checked_mul(&T::from(4)),
+        ) else {
+            return self.widened();
+        };
+        if squared < record {
+            return Outcome::Never;
+        }
+        let discriminant = squared - record;
+        let root = discriminant.sqrt();
+        let half = self.time.clone() / two.clone();
+        let mut lo = (self.time.clone() - root.clone()) / two.clone();
+        while lo <= half && !self.beats(&lo) {
+            lo = lo + T::one();
+        }
+        if lo <= half {
+            let hi = self.time.clone() - lo.clone();
+            Outcome::Wins(lo..=hi)
+        } else if root.clone() * root.clone() == discriminant
+            && (self.time.clone() - root.clone()).is_even()
+        {
+            let lo = (self.time.clone() - root.clone()) / two.clone();
+            let hi = (self.time.clone() + root) / two;
+            Outcome::Tie(lo..=hi)
+        } else {
+            Outcome::Never
+        }
+    }
+    fn widened(&self) -> Outcome<T> {
+        let wide = Race::<BigUint>::new(self.time.clone().into(), self.distance.clone().into());
+        let narrow = |holds: RangeInclusive<BigUint>| {
+            let (lo, hi) = holds.into_inner();
+            match (T::try_from(lo), T::try_from(hi)) {
+                (Ok(lo), Ok(hi)) => lo..=hi,
+                _ => unreachable!("holds never exceed the race time"),
+            }
+        };
+        match wide.outcome() {
+            Outcome::Wins(holds) => Outcome::Wins(narrow(holds)),
+            Outcome::Tie(holds) => Outcome::Tie(narrow(holds)),
+            Outcome::Never => Outcome::Never,
+        }
+    }
+    fn ways(&self) -> T {
+        match self.outcome() {
+            Outcome::Wins(holds) => holds.end().clone() - holds.start().clone() + T::one(),
+            Outcome::Tie(_) | Outcome::Never => T::zero(),
+        }
+    }
 }
 
-fn parse(input: &str) -> Option<Vec<Race>> {
-    let mut lines = input.lines();
-    let time_it = lines
-        .next()?
-        .strip_prefix("Time:")?
-        .split_whitespace()
-        .map(str::parse)
-        .filter_map(Result::ok);
-    let dist_it = lines
-        .next()?
-        .strip_prefix("Distance:")?
+#[derive(Debug)]
+struct Sheet {
+    races: Vec<Race<u128>>,
+    kerned: Race<BigUint>,
+}
+
+fn columns<'a>(line: Option<&'a str>, label: &str) -> anyhow::Result<Vec<&'a str>> {
+    let values = line
+        .ok_or_else(|| anyhow::anyhow!("missing {label} line"))?
+        .strip_prefix(label)
+        .ok_or_else(|| anyhow::anyhow!("expected line starting with {label}"))?
         .split_whitespace()
-        .map(str::parse)
-        .filter_map(Result::ok);
-    Some(
-        time_it
-            .zip(dist_it)
-            .map(|(time, distance)| Race::new(time, distance))
-            .collect(),
-    )
+        .collect::<Vec<_>>();
+    if let Some(token) = values
+        .iter()
+        .find(|token| !token.bytes().all(|b| b.is_ascii_digit()))
+    {
+        return Err(anyhow::anyhow!(
+            "non-numeric token {token:?} in {label} line"
+        ));
+    }
+    if values.is_empty() {
+        return Err(anyhow::anyhow!("no values in {label} line"));
+    }
+    Ok(values)
 }
-fn part1(races: &[Race]) -> usize {
-    races
+fn parse(input: &str) -> anyhow::Result<Sheet> {
+    let mut lines = input.lines();
+    let times = columns(lines.next(), "Time:")?;
+    let distances = columns(lines.next(), "Distance:")?;
+    if times.len() != distances.len() {
+        return Err(anyhow::anyhow!(
+            "{} times but {} distances",
+            times.len(),
+            distances.len()
+        ));
+    }
+    let races = times
         .iter()
-        .map(|race| {
-            (0..race.time)
-                .map(|v| v * (race.time - v))
-                .filter(|s| s > &race.distance)
-                .count()
-        })
-        .product()
+        .zip(&distances)
+        .map(|(time, distance)| Ok(Race::new(time.parse()?, distance.parse()?)))
+        .collect::<anyhow::Result<_>>()?;
+    let kerned = Race::new(times.concat().parse()?, distances.concat().parse()?);
+    Ok(Sheet { races, kerned })
+}
+fn part1(races: &[Race<u128>]) -> u128 {
+    races.iter().map(Race::ways).product()
 }
 
 fn main() {
     let input = read_to_string("inputs/day06-input1.txt").unwrap();
-    let races = parse(&input).unwrap();
-    let answer = part1(&races);
+    let sheet = parse(&input).unwrap();
+    if std::env::args().any(|arg| arg == "--outcomes") {
+        for race in &sheet.races {
+            println!("{race:?}: {:?}", race.outcome());
+        }
+        println!("kerned {:?}: {:?}", sheet.kerned, sheet.kerned.outcome());
+    }
+    let answer = part1(&sheet.races);
     println!("answer is: {answer}");
 }
 #[cfg(test)]
@@ -62,15 +153,44 @@ Distance:  9  40  200
 "#;
     #[test]
     fn parsing() {
-        let races = parse(INPUT.trim()).unwrap();
+        let sheet = parse(INPUT.trim()).unwrap();
         assert_eq!(
-            races,
+            sheet.races,
             vec![Race::new(7, 9), Race::new(15, 40), Race::new(30, 200)]
         );
+        assert_eq!(
+            sheet.kerned,
+            Race::new(BigUint::from(71530_u32), BigUint::from(940200_u32))
+        );
+    }
+    #[test]
+    fn parsing_errors() {
+        assert!(parse("Time: 7 15\nDistance: 9").is_err());
+        assert!(parse("Time: 7 1x5\nDistance: 9 40").is_err());
+        assert!(parse("Time: 7 15").is_err());
+        assert!(parse("Time:\nDistance:").is_err());
     }
     #[test]
     fn part1_test() {
-        let races = parse(INPUT.trim()).unwrap();
-        assert_eq!(part1(&races), 288);
+        let sheet = parse(INPUT.trim()).unwrap();
+        assert_eq!(part1(&sheet.races), 288);
+    }
+    #[test]
+    fn outcome_test() {
+        assert_eq!(Race::<u64>::new(7, 9).outcome(), Outcome::Wins(2..=5));
+        assert_eq!(Race::<u64>::new(30, 200).outcome(), Outcome::Wins(11..=19));
+        assert_eq!(Race::<u64>::new(4, 4).outcome(), Outcome::Tie(2..=2));
+        assert_eq!(Race::<u64>::new(4, 5).outcome(), Outcome::Never);
+        assert_eq!(Race::<u64>::new(5, 6).outcome(), Outcome::Tie(2..=3));
+        assert_eq!(Race::<u64>::new(5, 7).outcome(), Outcome::Never);
+        assert_eq!(Race::<u64>::new(0, 0).outcome(), Outcome::Tie(0..=0));
+        let time = 1_u128 << 70;
+        assert_eq!(Race::new(time, 5).ways(), time - 1);
+        assert_eq!(Race::new(u128::MAX, 0).ways(), u128::MAX - 1);
+        let time = (1_u128 << 64) + 2;
+        assert_eq!(
+            Race::new(time, (time / 2) * (time / 2)).outcome(),
+            Outcome::Tie(time / 2..=time / 2)
+        );
     }
 }
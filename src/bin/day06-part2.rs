@@ -1,53 +1,152 @@
 use std::fs::read_to_string;
+use std::ops::RangeInclusive;
+
+use num::integer::Roots;
+use num::{BigUint, CheckedMul, Integer};
 
 #[derive(Debug, PartialEq, Eq)]
-struct Race {
-    time: u64,
-    distance: u64,
+struct Race<T> {
+    time: T,
+    distance: T,
 }
 
-impl Race {
-    fn new(time: u64, distance: u64) -> Self {
+#[derive(Debug, PartialEq, Eq)]
+enum Outcome<T> {
+    Wins(RangeInclusive<T>),
+    Tie(RangeInclusive<T>),
+    Never,
+}
+
+impl<T> Race<T>
+where
+    T: Clone + Integer + Roots + From<u8> + CheckedMul + Into<BigUint> + TryFrom<BigUint>,
+{
+    fn new(time: T, distance: T) -> Self {
         Self { time, distance }
     }
+    fn beats(&self, hold: &T) -> bool {
+        hold.clone() * (self.time.clone() - hold.clone()) > self.distance
+    }
+    fn outcome(&self) -> Outcome<T> {
+        let two = T::from(2);
+        let (Some(squared), Some(record)) = (
+            self.time.checked_mul(&self.time),
+            self.distance.checked_mul(&T::from(4)),
+        ) else {
+            return self.widened();
+        };
+        if squared < record {
+            return Outcome::Never;
+        }
+        let discriminant = squared - record;
+        let root = discriminant.sqrt();
+        let half = self.time.clone() / two.clone();
+        let mut lo = (self.time.clone() - root.clone()) / two.clone();
+        while lo <= half && !self.beats(&lo) {
+            lo = lo + T::one();
+        }
+        if lo <= half {
+            let hi = self.time.clone() - lo.clone();
+            Outcome::Wins(lo..=hi)
+        } else if root.clone() * root.clone() == discriminant
+            && (self.time.clone() - root.clone()).is_even()
+        {
+            let lo = (self.time.clone() - root.clone()) / two.clone();
+            let hi = (self.time.clone() + root) / two;
+            Outcome::Tie(lo..=hi)
+        } else {
+            Outcome::Never
+        }
+    }
+    fn widened(&self) -> Outcome<T> {
+        let wide = Race::<BigUint>::new(self.time.clone().into(), self.distance.clone().into());
+        let narrow = |holds: RangeInclusive<BigUint>| {
+            let (lo, hi) = holds.into_inner();
+            match (T::try_from(lo), T::try_from(hi)) {
+                (Ok(lo), Ok(hi)) => lo..=hi,
+                _ => unreachable!("holds never exceed the race time"),
+            }
+        };
+        match wide.outcome() {
+            Outcome::Wins(holds) => Outcome::Wins(narrow(holds)),
+            Outcome::Tie(holds) => Outcome::Tie(narrow(holds)),
+            Outcome::Never => Outcome::Never,
+        }
+    }
+    fn ways(&self) -> T {
+        match self.outcome() {
+            Outcome::Wins(holds) => holds.end().clone() - holds.start().clone() + T::one(),
+            Outcome::Tie(_) | Outcome::Never => T::zero(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Sheet {
+    races: Vec<Race<u128>>,
+    kerned: Race<BigUint>,
 }
 
-fn parse(input: &str) -> Option<Race> {
+fn columns<'a>(line: Option<&'a str>, label: &str) -> anyhow::Result<Vec<&'a str>> {
+    let values = line
+        .ok_or_else(|| anyhow::anyhow!("missing {label} line"))?
+        .strip_prefix(label)
+        .ok_or_else(|| anyhow::anyhow!("expected line starting with {label}"))?
+        .split_whitespace()
+        .collect::<Vec<_>>();
+    if let Some(token) = values
+        .iter()
+        .find(|token| !token.bytes().all(|b| b.is_ascii_digit()))
+    {
+        return Err(anyhow::anyhow!(
+            "non-numeric token {token:?} in {label} line"
+        ));
+    }
+    if values.is_empty() {
+        return Err(anyhow::anyhow!("no values in {label} line"));
+    }
+    Ok(values)
+}
+fn parse(input: &str) -> anyhow::Result<Sheet> {
     let mut lines = input.lines();
-    let time = lines
-        .next()?
-        .strip_prefix("Time:")?
-        .chars()
-        .filter(|c| !c.is_whitespace())
-        .collect::<String>()
-        .parse()
-        .ok()?;
-    let dist = lines
-        .next()?
-        .strip_prefix("Distance:")?
-        .chars()
-        .filter(|c| !c.is_whitespace())
-        .collect::<String>()
-        .parse()
-        .ok()?;
-    Some(Race::new(time, dist))
+    let times = columns(lines.next(), "Time:")?;
+    let distances = columns(lines.next(), "Distance:")?;
+    if times.len() != distances.len() {
+        return Err(anyhow::anyhow!(
+            "{} times but {} distances",
+            times.len(),
+            distances.len()
+        ));
+    }
+    let races = times
+        .iter()
+        .zip(&distances)
+        .map(|(time, distance)| Ok(Race::new(time.parse()?, distance.parse()?)))
+        .collect::<anyhow::Result<_>>()?;
+    let kerned = Race::new(times.concat().parse()?, distances.concat().parse()?);
+    Ok(Sheet { races, kerned })
 }
-fn part1(race: &Race) -> usize {
-    (14..=race.time - 14)
-        .map(|v| v * (race.time - v))
-        .filter(|s| s > &race.distance)
-        .count()
+fn part1(race: &Race<BigUint>) -> BigUint {
+    race.ways()
 }
 
 fn main() {
     let input = read_to_string("inputs/day06-input1.txt").unwrap();
-    let races = parse(&input).unwrap();
-    let answer = part1(&races);
+    let sheet = parse(&input).unwrap();
+    if std::env::args().any(|arg| arg == "--outcomes") {
+        for race in &sheet.races {
+            println!("{race:?}: {:?}", race.outcome());
+        }
+        println!("kerned {:?}: {:?}", sheet.kerned, sheet.kerned.outcome());
+    }
+    let answer = part1(&sheet.kerned);
     println!("answer is: {answer}");
 }
 #[cfg(test)]
 mod tests {
 
+    use num::{One, Zero};
+
     use super::*;
 
     const INPUT: &str = r#"
@@ -56,12 +155,35 @@ Distance:  9  40  200
 "#;
     #[test]
     fn parsing() {
-        let races = parse(INPUT.trim()).unwrap();
-        assert_eq!(races, Race::new(71530, 940200));
+        let sheet = parse(INPUT.trim()).unwrap();
+        assert_eq!(sheet.kerned, Race::new(71530_u32.into(), 940200_u32.into()));
+        assert_eq!(sheet.races.len(), 3);
+    }
+    #[test]
+    fn parsing_errors() {
+        assert!(parse("Time: 7 15\nDistance: 9").is_err());
+        assert!(parse("Time: 7 1x5\nDistance: 9 40").is_err());
+        assert!(parse("Time: 7 15").is_err());
+        assert!(parse("Time:\nDistance:").is_err());
     }
     #[test]
     fn part1_test() {
-        let races = parse(INPUT.trim()).unwrap();
-        assert_eq!(part1(&races), 71503);
+        let sheet = parse(INPUT.trim()).unwrap();
+        assert_eq!(part1(&sheet.kerned), 71503_u32.into());
+    }
+    #[test]
+    fn huge_race() {
+        let time = BigUint::from(10_u8).pow(40);
+        let race = Race::new(time.clone(), BigUint::zero());
+        assert_eq!(race.ways(), time.clone() - 1_u8);
+        let tie = Race::new(time.clone(), time.pow(2) / 4_u8);
+        assert_eq!(
+            tie.outcome(),
+            Outcome::Tie(time.clone() / 2_u8..=time.clone() / 2_u8)
+        );
+        let never = Race::new(time.clone(), time.pow(2) / 4_u8 + 1_u8);
+        assert_eq!(never.outcome(), Outcome::Never);
+        let close = Race::new(time.clone(), time.pow(2) / 4_u8 - 1_u8);
+        assert_eq!(close.ways(), BigUint::one());
     }
 }
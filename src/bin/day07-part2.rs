@@ -1,17 +1,18 @@
 #![allow(dead_code)]
 use std::cmp::Ordering;
+use std::fmt::Display;
 use std::fs::read_to_string;
 use std::str::FromStr;
 
 use anyhow::{anyhow, Ok};
 use itertools::Itertools;
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
-#[repr(u8)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 enum Card {
     A,
     K,
     Q,
+    J,
     T,
     Nine,
     Eight,
@@ -21,12 +22,8 @@ enum Card {
     Four,
     Three,
     Two,
-    J,
 }
 impl Card {
-    fn strength(&self) -> u8 {
-        255 - *self as u8
-    }
     fn from_char(c: char) -> Self {
         match c {
             'A' => Self::A,
@@ -45,9 +42,25 @@ impl Card {
             _ => unimplemented!("no such card"),
         }
     }
+    fn to_char(self) -> char {
+        match self {
+            Self::A => 'A',
+            Self::K => 'K',
+            Self::Q => 'Q',
+            Self::J => 'J',
+            Self::T => 'T',
+            Self::Nine => '9',
+            Self::Eight => '8',
+            Self::Seven => '7',
+            Self::Six => '6',
+            Self::Five => '5',
+            Self::Four => '4',
+            Self::Three => '3',
+            Self::Two => '2',
+        }
+    }
 }
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
-#[repr(u8)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 enum HandType {
     Five,
     Four,
@@ -57,96 +70,212 @@ enum HandType {
     OnePair,
     High,
 }
-impl HandType {
-    fn strength(&self) -> u8 {
-        255 - *self as u8
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Hand(Vec<Card>);
+impl Display for Hand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0.iter().map(|card| card.to_char()).join(""))
     }
 }
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-struct Hand([Card; 5]);
-impl PartialOrd for Hand {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
-    }
+
+type Key = (usize, Vec<usize>);
+
+#[derive(Debug, Clone)]
+struct Rules {
+    order: Vec<Card>,
+    wild: Vec<Card>,
+    hand_size: usize,
+    categories: Vec<(HandType, Vec<usize>)>,
 }
-impl Ord for Hand {
-    fn cmp(&self, other: &Self) -> Ordering {
-        match self.handtype().strength().cmp(&other.handtype().strength()) {
-            Ordering::Less => Ordering::Less,
-            Ordering::Equal => self
-                .0
-                .iter()
-                .map(Card::strength)
-                .cmp(other.0.iter().map(Card::strength)),
-            Ordering::Greater => Ordering::Greater,
+impl Rules {
+    fn new(
+        order: Vec<Card>,
+        wild: Vec<Card>,
+        hand_size: usize,
+        categories: Vec<(HandType, Vec<usize>)>,
+    ) -> Self {
+        let categories = categories
+            .into_iter()
+            .map(|(handtype, pattern)| (handtype, pattern.into_iter().sorted().rev().collect()))
+            .collect();
+        Self {
+            order,
+            wild,
+            hand_size,
+            categories,
         }
     }
-}
-impl From<Vec<Card>> for Hand {
-    fn from(v: Vec<Card>) -> Self {
-        Self([v[0], v[1], v[2], v[3], v[4]])
+    fn standard() -> Self {
+        use Card::*;
+        Self::new(
+            vec![
+                Two, Three, Four, Five, Six, Seven, Eight, Nine, T, J, Q, K, A,
+            ],
+            vec![],
+            5,
+            vec![
+                (HandType::Five, vec![5]),
+                (HandType::Four, vec![4, 1]),
+                (HandType::FullHouse, vec![3, 2]),
+                (HandType::Three, vec![3, 1, 1]),
+                (HandType::TwoPair, vec![2, 2, 1]),
+                (HandType::OnePair, vec![2, 1, 1, 1]),
+                (HandType::High, vec![1, 1, 1, 1, 1]),
+            ],
+        )
     }
-}
-impl Hand {
-    fn handtype(&self) -> HandType {
-        let dedup_it = self
-            .0
+    fn jokers() -> Self {
+        use Card::*;
+        Self {
+            order: vec![
+                J, Two, Three, Four, Five, Six, Seven, Eight, Nine, T, Q, K, A,
+            ],
+            wild: vec![J],
+            ..Self::standard()
+        }
+    }
+    fn strength(&self, card: Card) -> Option<usize> {
+        self.order.iter().position(|&c| c == card)
+    }
+    fn groups(&self, hand: &Hand) -> Vec<(usize, Card)> {
+        hand.0
             .iter()
-            .sorted()
-            .dedup_with_count()
-            .sorted_by_key(|(count, _card)| -(*count as isize));
-        let jokers = dedup_it
-            .clone()
-            .find(|(_, card)| **card == Card::J)
+            .filter(|c| !self.wild.contains(c))
+            .counts()
+            .into_iter()
+            .map(|(&card, count)| (count, card))
+            .sorted_by_key(|&(count, card)| (count, self.strength(card)))
+            .rev()
+            .collect()
+    }
+    fn category(&self, hand: &Hand) -> Option<usize> {
+        if hand.0.len() != self.hand_size {
+            return None;
+        }
+        let wilds = hand.0.iter().filter(|c| self.wild.contains(c)).count();
+        let counts = self
+            .groups(hand)
+            .into_iter()
             .map(|(count, _)| count)
-            .unwrap_or(0);
-        let mut dedup_it = dedup_it.map(|(count, _)| count);
-        match dedup_it.next() {
-            Some(5) => HandType::Five,
-            Some(4) => {
-                if jokers != 0 {
-                    HandType::Five
-                } else {
-                    HandType::Four
-                }
-            }
-            Some(3) => {
-                if let Some(2) = dedup_it.next() {
-                    if jokers != 0 {
-                        HandType::Five
-                    } else {
-                        HandType::FullHouse
-                    }
-                } else if jokers != 0 {
-                    HandType::Four
-                } else {
-                    HandType::Three
-                }
-            }
-            Some(2) => {
-                if let Some(2) = dedup_it.next() {
-                    if jokers == 2 {
-                        HandType::Four
-                    } else if jokers == 1 {
-                        HandType::FullHouse
+            .collect_vec();
+        self.categories.iter().position(|(_, pattern)| {
+            pattern.iter().sum::<usize>() == counts.iter().sum::<usize>() + wilds
+                && counts.len() <= pattern.len()
+                && pattern.iter().zip(&counts).all(|(p, c)| p >= c)
+        })
+    }
+    fn handtype(&self, hand: &Hand) -> Option<HandType> {
+        self.category(hand).map(|i| self.categories[i].0)
+    }
+    fn key(&self, hand: &Hand) -> anyhow::Result<Key> {
+        let category = self
+            .category(hand)
+            .ok_or_else(|| anyhow!("no hand category matches {hand:?}"))?;
+        let strengths = hand
+            .0
+            .iter()
+            .map(|&card| {
+                self.strength(card)
+                    .ok_or_else(|| anyhow!("card {card:?} not in card order"))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok((self.categories.len() - category, strengths))
+    }
+    fn cmp(&self, a: &Hand, b: &Hand) -> anyhow::Result<Ordering> {
+        Ok(self.key(a)?.cmp(&self.key(b)?))
+    }
+    fn substitute(&self, hand: &Hand) -> Option<Hand> {
+        let groups = self.groups(hand);
+        let (_, pattern) = &self.categories[self.category(hand)?];
+        let mut spare =
+            self.order.iter().rev().filter(|card| {
+                !self.wild.contains(card) && !groups.iter().any(|(_, c)| c == *card)
+            });
+        let mut fills = vec![];
+        for (i, &size) in pattern.iter().enumerate() {
+            let (count, card) = match groups.get(i) {
+                Some(&group) => group,
+                None => (0, *spare.next()?),
+            };
+            fills.extend(std::iter::repeat_n(card, size - count));
+        }
+        let mut fills = fills.into_iter();
+        Some(Hand(
+            hand.0
+                .iter()
+                .map(|&card| {
+                    if self.wild.contains(&card) {
+                        fills.next().unwrap_or(card)
                     } else {
-                        HandType::TwoPair
+                        card
                     }
-                } else if jokers != 0 {
-                    HandType::Three
-                } else {
-                    HandType::OnePair
-                }
-            }
-            Some(1) => {
-                if jokers != 0 {
-                    HandType::OnePair
-                } else {
-                    HandType::High
-                }
-            }
-            _ => unreachable!(),
-        }
+                })
+                .collect(),
+        ))
+    }
+    fn report(&self, games: &[Game]) -> anyhow::Result<Vec<Entry>> {
+        let ranked = games
+            .iter()
+            .map(|game| Ok((game, self.key(&game.hand)?)))
+            .collect::<anyhow::Result<Vec<_>>>()?
+            .into_iter()
+            .sorted_by(|(_, a), (_, b)| a.cmp(b))
+            .collect_vec();
+        let tie_break = |key: &Key, other: Option<&(&Game, Key)>| {
+            other
+                .filter(|(_, other)| other.0 == key.0)
+                .and_then(|(_, other)| key.1.iter().zip(&other.1).position(|(a, b)| a != b))
+        };
+        ranked
+            .iter()
+            .enumerate()
+            .map(|(i, (game, key))| {
+                Ok(Entry {
+                    hand: game.hand.clone(),
+                    bid: game.bid,
+                    handtype: self.categories[self.categories.len() - key.0].0,
+                    substitution: self
+                        .substitute(&game.hand)
+                        .ok_or_else(|| anyhow!("no substitution for {}", game.hand))?,
+                    rank: i + 1,
+                    below: tie_break(key, i.checked_sub(1).map(|j| &ranked[j])),
+                    above: tie_break(key, ranked.get(i + 1)),
+                })
+            })
+            .collect()
+    }
+    fn winnings(&self, games: &[Game]) -> anyhow::Result<u32> {
+        Ok(self
+            .report(games)?
+            .iter()
+            .map(|entry| entry.rank as u32 * entry.bid)
+            .sum())
+    }
+}
+#[derive(Debug, PartialEq, Eq)]
+struct Entry {
+    hand: Hand,
+    bid: u32,
+    handtype: HandType,
+    substitution: Hand,
+    rank: usize,
+    below: Option<usize>,
+    above: Option<usize>,
+}
+impl Display for Entry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let position = |tie: Option<usize>| tie.map_or("-".to_string(), |i| (i + 1).to_string());
+        write!(
+            f,
+            "{:>5} {} as {} {:?} bid {} tie-break below {} above {}",
+            self.rank,
+            self.hand,
+            self.substitution,
+            self.handtype,
+            self.bid,
+            position(self.below),
+            position(self.above)
+        )
     }
 }
 #[derive(Debug)]
@@ -163,12 +292,7 @@ impl FromStr for Game {
             it.next().ok_or(anyhow!("failed to parse hand"))?,
             it.next().ok_or(anyhow!("failed to parse bid"))?.parse()?,
         );
-        let hand = hand
-            .chars()
-            .map(Card::from_char)
-            .collect::<Vec<_>>()
-            .try_into()
-            .unwrap();
+        let hand = Hand(hand.chars().map(Card::from_char).collect());
         Ok(Game { hand, bid })
     }
 }
@@ -180,16 +304,17 @@ fn parse(input: &str) -> Vec<Game> {
         .collect()
 }
 fn part2(games: &[Game]) -> u32 {
-    games
-        .iter()
-        .sorted_by(|a, b| a.hand.cmp(&b.hand))
-        .enumerate()
-        .fold(0, |acc, (i, game)| acc + ((i as u32 + 1) * game.bid))
+    Rules::jokers().winnings(games).unwrap()
 }
 
 fn main() {
     let input = read_to_string("inputs/day07-input1.txt").unwrap();
     let games = parse(&input);
+    if std::env::args().any(|arg| arg == "--report") {
+        for entry in Rules::jokers().report(&games).unwrap() {
+            println!("{entry}");
+        }
+    }
     let answer = part2(&games);
     println!("answer is: {answer}");
 }
@@ -209,55 +334,115 @@ QQQJA 483
     fn parsing() {
         let games = parse(INPUT.trim());
         use Card::*;
-        assert_eq!(games[0].hand, Hand([Three, Two, T, Three, K]));
+        assert_eq!(games[0].hand, Hand(vec![Three, Two, T, Three, K]));
         assert_eq!(games[0].bid, 765);
     }
     #[test]
     fn strength_card_test() {
-        assert!(Card::A.strength() > Card::K.strength());
-        assert!(Card::Q.strength() > Card::Three.strength());
+        let rules = Rules::jokers();
+        assert!(rules.strength(Card::A) > rules.strength(Card::K));
+        assert!(rules.strength(Card::Q) > rules.strength(Card::Three));
+        assert!(rules.strength(Card::Two) > rules.strength(Card::J));
     }
     #[test]
     fn strength_hand_test() {
-        assert!(HandType::Four.strength() > HandType::High.strength());
+        use Card::*;
+        let rules = Rules::jokers();
+        let four = Hand(vec![J, J, Two, Two, Three]);
+        let full_house = Hand(vec![A, A, A, K, K]);
+        assert_eq!(rules.cmp(&four, &full_house).unwrap(), Ordering::Greater);
     }
     #[test]
     fn handtype_test() {
         use Card::*;
-        let hand = Hand([A, A, A, A, J]);
-        assert_eq!(hand.handtype(), HandType::Five);
-        let hand = Hand([A, A, A, J, J]);
-        assert_eq!(hand.handtype(), HandType::Five);
-        let hand = Hand([A, A, J, J, J]);
-        assert_eq!(hand.handtype(), HandType::Five);
-        let hand = Hand([A, J, J, J, J]);
-        assert_eq!(hand.handtype(), HandType::Five);
+        let rules = Rules::jokers();
+        let hand = Hand(vec![A, A, A, A, J]);
+        assert_eq!(rules.handtype(&hand), Some(HandType::Five));
+        let hand = Hand(vec![A, A, A, J, J]);
+        assert_eq!(rules.handtype(&hand), Some(HandType::Five));
+        let hand = Hand(vec![A, A, J, J, J]);
+        assert_eq!(rules.handtype(&hand), Some(HandType::Five));
+        let hand = Hand(vec![A, J, J, J, J]);
+        assert_eq!(rules.handtype(&hand), Some(HandType::Five));
+        let hand = Hand(vec![J, J, J, J, J]);
+        assert_eq!(rules.handtype(&hand), Some(HandType::Five));
 
-        let hand = Hand([A, A, K, J, A]);
-        assert_eq!(hand.handtype(), HandType::Four);
-        let hand = Hand([A, J, K, J, J]);
-        assert_eq!(hand.handtype(), HandType::Four);
+        let hand = Hand(vec![A, A, K, J, A]);
+        assert_eq!(rules.handtype(&hand), Some(HandType::Four));
+        let hand = Hand(vec![A, J, K, J, J]);
+        assert_eq!(rules.handtype(&hand), Some(HandType::Four));
 
-        let hand = Hand([A, J, K, K, A]);
-        assert_eq!(hand.handtype(), HandType::FullHouse);
+        let hand = Hand(vec![A, J, K, K, A]);
+        assert_eq!(rules.handtype(&hand), Some(HandType::FullHouse));
 
-        let hand = Hand([A, J, K, Q, A]);
-        assert_eq!(hand.handtype(), HandType::Three);
-        let hand = Hand([A, J, K, Q, J]);
-        assert_eq!(hand.handtype(), HandType::Three);
+        let hand = Hand(vec![A, J, K, Q, A]);
+        assert_eq!(rules.handtype(&hand), Some(HandType::Three));
+        let hand = Hand(vec![A, J, K, Q, J]);
+        assert_eq!(rules.handtype(&hand), Some(HandType::Three));
 
-        let hand = Hand([A, K, K, Q, A]);
-        assert_eq!(hand.handtype(), HandType::TwoPair);
+        let hand = Hand(vec![A, K, K, Q, A]);
+        assert_eq!(rules.handtype(&hand), Some(HandType::TwoPair));
 
-        let hand = Hand([A, Nine, K, Q, J]);
-        assert_eq!(hand.handtype(), HandType::OnePair);
+        let hand = Hand(vec![A, Nine, K, Q, J]);
+        assert_eq!(rules.handtype(&hand), Some(HandType::OnePair));
 
-        let hand = Hand([T, Nine, K, Q, A]);
-        assert_eq!(hand.handtype(), HandType::High);
+        let hand = Hand(vec![T, Nine, K, Q, A]);
+        assert_eq!(rules.handtype(&hand), Some(HandType::High));
+    }
+    #[test]
+    fn house_rules_test() {
+        use Card::*;
+        let rules = Rules::new(
+            vec![
+                Three, Four, Five, Six, Seven, Eight, Nine, T, J, Q, K, A, Two,
+            ],
+            vec![Two],
+            4,
+            vec![
+                (HandType::Four, vec![4]),
+                (HandType::TwoPair, vec![2, 2]),
+                (HandType::Three, vec![1, 3]),
+                (HandType::OnePair, vec![2, 1, 1]),
+                (HandType::High, vec![1, 1, 1, 1]),
+            ],
+        );
+        let hand = Hand(vec![A, Two, K, K]);
+        assert_eq!(rules.handtype(&hand), Some(HandType::TwoPair));
+        let hand = Hand(vec![A, Two, Two, K]);
+        assert_eq!(rules.handtype(&hand), Some(HandType::TwoPair));
+        let hand = Hand(vec![A, K, Q, Two]);
+        assert_eq!(rules.handtype(&hand), Some(HandType::OnePair));
+        let hand = Hand(vec![A, K, Q, Two, Two]);
+        assert_eq!(rules.handtype(&hand), None);
     }
     #[test]
     fn handrank() {}
     #[test]
+    fn report_test() {
+        use Card::*;
+        let games = parse(INPUT.trim());
+        let report = Rules::jokers().report(&games).unwrap();
+        let ktjjt = &report[4];
+        assert_eq!(ktjjt.hand, Hand(vec![K, T, J, J, T]));
+        assert_eq!(ktjjt.substitution, Hand(vec![K, T, T, T, T]));
+        assert_eq!(ktjjt.handtype, HandType::Four);
+        assert_eq!((ktjjt.rank, ktjjt.below, ktjjt.above), (5, Some(0), None));
+        assert_eq!(
+            (report[2].rank, report[2].below, report[2].above),
+            (3, None, Some(0))
+        );
+        assert_eq!(
+            report[0].to_string(),
+            "    1 32T3K as 32T3K OnePair bid 765 tie-break below - above -"
+        );
+        assert_eq!(report[3].substitution.to_string(), "QQQQA");
+        let jokers = Hand(vec![J, J, J, J, J]);
+        assert_eq!(
+            Rules::jokers().substitute(&jokers),
+            Some(Hand(vec![A, A, A, A, A]))
+        );
+    }
+    #[test]
     fn part2_test() {
         let games = parse(INPUT.trim());
         assert_eq!(part2(&games), 5905);
@@ -1,11 +1,15 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::read_to_string;
 
+use itertools::Itertools;
 use nom::bytes::complete::{tag, take};
 use nom::character::complete::{line_ending, one_of};
 use nom::multi::{many1, separated_list1};
 use nom::sequence::{delimited, separated_pair, terminated};
 use nom::{Finish, Parser};
+use petgraph::algo::tarjan_scc;
+use petgraph::graphmap::DiGraphMap;
+use petgraph::visit::Dfs;
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
 enum Dir {
@@ -14,7 +18,7 @@ enum Dir {
 }
 type Map<'a> = HashMap<&'a str, (&'a str, &'a str)>;
 
-fn parse(input: &str) -> anyhow::Result<(Vec<Dir>, Map)> {
+fn parse(input: &str) -> anyhow::Result<(Vec<Dir>, Map<'_>)> {
     let lr = one_of("LR").map(|c| match c {
         'L' => Dir::L,
         'R' => Dir::R,
@@ -32,25 +36,109 @@ fn parse(input: &str) -> anyhow::Result<(Vec<Dir>, Map)> {
     Ok((dirs, map.into_iter().collect()))
 }
 
-fn part1(dirs: &[Dir], map: &Map) -> usize {
-    let mut pos = "AAA";
-    dirs.iter()
-        .cycle()
-        .take_while(|dir| {
-            pos = match dir {
-                Dir::L => map[&pos].0,
-                Dir::R => map[&pos].1,
+struct Network<'a> {
+    dirs: &'a [Dir],
+    map: &'a Map<'a>,
+    graph: DiGraphMap<&'a str, ()>,
+}
+impl<'a> Network<'a> {
+    fn new(dirs: &'a [Dir], map: &'a Map<'a>) -> anyhow::Result<Self> {
+        if dirs.is_empty() {
+            return Err(anyhow::anyhow!("empty instruction string"));
+        }
+        let mut graph = DiGraphMap::new();
+        for (&node, &(left, right)) in map.iter().sorted() {
+            for next in [left, right] {
+                if !map.contains_key(next) {
+                    return Err(anyhow::anyhow!("node {node} points to unknown node {next}"));
+                }
+                graph.add_edge(node, next, ());
+            }
+        }
+        Ok(Self { dirs, map, graph })
+    }
+    fn reachable(&self, from: &'a str) -> HashSet<&'a str> {
+        let mut dfs = Dfs::new(&self.graph, from);
+        let mut seen = HashSet::new();
+        while let Some(node) = dfs.next(&self.graph) {
+            seen.insert(node);
+        }
+        seen
+    }
+    fn components(&self) -> Vec<Vec<&'a str>> {
+        tarjan_scc(&self.graph)
+            .into_iter()
+            .map(|component| component.into_iter().sorted().collect())
+            .collect()
+    }
+    fn walk(&self, from: &'a str) -> impl Iterator<Item = (usize, &'a str)> + '_ {
+        let mut seen = HashSet::new();
+        let mut state = (from, 0);
+        (0..).map_while(move |step| {
+            if !self.map.contains_key(state.0) || !seen.insert(state) {
+                return None;
+            }
+            let (node, i) = state;
+            state.0 = match self.dirs[i] {
+                Dir::L => self.map[node].0,
+                Dir::R => self.map[node].1,
             };
-            pos != "ZZZ"
+            state.1 = (i + 1) % self.dirs.len();
+            Some((step, node))
         })
-        .count()
-        + 1
+    }
+    fn targets(&self, from: &'a str) -> Vec<&'a str> {
+        self.walk(from)
+            .map(|(_, node)| node)
+            .filter(|node| node.ends_with('Z'))
+            .unique()
+            .sorted()
+            .collect()
+    }
+    fn never_terminating(&self) -> Vec<&'a str> {
+        self.map
+            .keys()
+            .copied()
+            .filter(|start| start.ends_with('A') && self.targets(start).is_empty())
+            .sorted()
+            .collect()
+    }
+    fn steps(&self, from: &'a str, to: &str) -> anyhow::Result<usize> {
+        if !self.map.contains_key(from) {
+            return Err(anyhow::anyhow!("unknown start node {from}"));
+        }
+        self.walk(from)
+            .skip(1)
+            .find(|&(_, node)| node == to)
+            .map(|(step, _)| step)
+            .ok_or_else(|| anyhow::anyhow!("{from} never reaches {to} following the instructions"))
+    }
+}
+
+fn part1(dirs: &[Dir], map: &Map) -> anyhow::Result<usize> {
+    Network::new(dirs, map)?.steps("AAA", "ZZZ")
 }
 
 fn main() {
     let input = read_to_string("inputs/day08-input1.txt").unwrap();
     let (dirs, map) = parse(&input).unwrap();
-    let answer = part1(&dirs, &map);
+    if std::env::args().any(|arg| arg == "--analyze") {
+        let network = Network::new(&dirs, &map).unwrap();
+        println!(
+            "nodes reachable from AAA: {}",
+            network.reachable("AAA").len()
+        );
+        println!(
+            "strongly connected components: {}",
+            network.components().len()
+        );
+        println!("targets walked into from AAA: {:?}", network.targets("AAA"));
+        println!(
+            "starts that never terminate: {:?}",
+            network.never_terminating()
+        );
+    }
+    let answer = part1(&dirs, &map).unwrap();
     println!("answer is: {answer}");
 }
 #[cfg(test)]
@@ -90,9 +178,35 @@ ZZZ = (ZZZ, ZZZ)
     #[test]
     fn part1_test() {
         let (dirs, map) = parse(INPUT1.trim()).unwrap();
-        assert_eq!(part1(&dirs, &map), 2);
+        assert_eq!(part1(&dirs, &map).unwrap(), 2);
 
         let (dirs, map) = parse(INPUT2.trim()).unwrap();
-        assert_eq!(part1(&dirs, &map), 6);
+        assert_eq!(part1(&dirs, &map).unwrap(), 6);
+    }
+    #[test]
+    fn analysis() {
+        let (dirs, map) = parse(INPUT1.trim()).unwrap();
+        let network = Network::new(&dirs, &map).unwrap();
+        assert_eq!(
+            network.reachable("BBB"),
+            HashSet::from(["BBB", "DDD", "EEE"])
+        );
+        let components = network.components();
+        assert_eq!(components.len(), 7);
+        assert!(components.contains(&vec!["ZZZ"]));
+        assert_eq!(network.targets("AAA"), vec!["ZZZ"]);
+        assert_eq!(network.never_terminating(), Vec::<&str>::new());
+    }
+    #[test]
+    fn invalid_networks() {
+        let (dirs, map) =
+            parse("L\n\nAAA = (BBB, ZZZ)\nBBB = (AAA, AAA)\nZZZ = (ZZZ, ZZZ)").unwrap();
+        let network = Network::new(&dirs, &map).unwrap();
+        assert_eq!(network.components(), vec![vec!["ZZZ"], vec!["AAA", "BBB"]]);
+        assert_eq!(network.never_terminating(), vec!["AAA"]);
+        assert!(part1(&dirs, &map).is_err());
+
+        let (dirs, map) = parse("L\n\nAAA = (BBB, ZZZ)\nZZZ = (ZZZ, ZZZ)").unwrap();
+        assert!(Network::new(&dirs, &map).is_err());
     }
 }
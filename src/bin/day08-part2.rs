@@ -1,12 +1,13 @@
 use std::collections::HashMap;
 use std::fs::read_to_string;
 
+use itertools::Itertools;
 use nom::bytes::complete::{tag, take};
 use nom::character::complete::{line_ending, one_of};
 use nom::multi::{many1, separated_list1};
 use nom::sequence::{delimited, separated_pair, terminated};
 use nom::{Finish, Parser};
-use num::integer::lcm;
+use num::integer::{ExtendedGcd, Integer};
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
 enum Dir {
@@ -15,7 +16,7 @@ enum Dir {
 }
 type Map<'a> = HashMap<&'a str, (&'a str, &'a str)>;
 
-fn parse(input: &str) -> anyhow::Result<(Vec<Dir>, Map)> {
+fn parse(input: &str) -> anyhow::Result<(Vec<Dir>, Map<'_>)> {
     let lr = one_of("LR").map(|c| match c {
         'L' => Dir::L,
         'R' => Dir::R,
@@ -33,34 +34,101 @@ fn parse(input: &str) -> anyhow::Result<(Vec<Dir>, Map)> {
     Ok((dirs, map.into_iter().collect()))
 }
 
-fn part2(dirs: &[Dir], map: &Map) -> usize {
-    map.keys()
-        .filter(|key| key.ends_with('A'))
-        .copied()
-        .map(|mut pos| {
-            let cu = &mut pos;
-            dirs.iter()
-                .cycle()
-                .map(|dir| {
-                    *cu = match dir {
-                        Dir::L => map[cu].0,
-                        Dir::R => map[cu].1,
-                    };
-                    *cu
-                })
-                .take_while(|pos| !pos.ends_with('Z'))
-                .count()
-                + 1
+#[derive(Debug, PartialEq, Eq)]
+struct Ghost {
+    prefix: usize,
+    cycle: usize,
+    prefix_hits: Vec<usize>,
+    cycle_hits: Vec<usize>,
+}
+impl Ghost {
+    fn trace(start: &str, dirs: &[Dir], map: &Map) -> Self {
+        let mut seen = HashMap::new();
+        let mut hits = vec![];
+        let (mut node, mut i, mut step) = (start, 0, 0);
+        let prefix = loop {
+            if let Some(&first) = seen.get(&(node, i)) {
+                break first;
+            }
+            seen.insert((node, i), step);
+            if node.ends_with('Z') {
+                hits.push(step);
+            }
+            node = match dirs[i] {
+                Dir::L => map[node].0,
+                Dir::R => map[node].1,
+            };
+            i = (i + 1) % dirs.len();
+            step += 1;
+        };
+        let (prefix_hits, cycle_hits) = hits.into_iter().partition(|&hit| hit < prefix);
+        Self {
+            prefix,
+            cycle: step - prefix,
+            prefix_hits,
+            cycle_hits,
+        }
+    }
+    fn arrives_at(&self, step: u128) -> bool {
+        let prefix = self.prefix as u128;
+        if step < prefix {
+            self.prefix_hits.contains(&(step as usize))
+        } else {
+            let offset = (step - prefix) % self.cycle as u128 + prefix;
+            self.cycle_hits.contains(&(offset as usize))
+        }
+    }
+}
+fn crt((a1, m1): (i128, i128), (a2, m2): (i128, i128)) -> Option<(i128, i128)> {
+    let ExtendedGcd { gcd, x, .. } = m1.extended_gcd(&m2);
+    if (a2 - a1) % gcd != 0 {
+        return None;
+    }
+    let modulus = m1 / gcd * m2;
+    let k = ((a2 - a1) / gcd * x).rem_euclid(m2 / gcd);
+    Some(((a1 + m1 * k).rem_euclid(modulus), modulus))
+}
+fn first_meeting(ghosts: &[Ghost]) -> Option<u128> {
+    let one_off = ghosts
+        .iter()
+        .flat_map(|ghost| ghost.prefix_hits.iter())
+        .map(|&hit| hit as u128)
+        .filter(|&hit| ghosts.iter().all(|ghost| ghost.arrives_at(hit)));
+    let periodic = ghosts
+        .iter()
+        .map(|ghost| {
+            ghost
+                .cycle_hits
+                .iter()
+                .map(|&hit| (hit as i128, ghost.cycle as i128))
         })
-        .reduce(lcm)
-        .unwrap()
+        .multi_cartesian_product()
+        .filter_map(|residues| {
+            let earliest = residues.iter().map(|&(hit, _)| hit).max()?;
+            let (residue, modulus) = residues.into_iter().try_fold((0, 1), crt)?;
+            let laps = (earliest - residue + modulus - 1)
+                .div_euclid(modulus)
+                .max(0);
+            Some((residue + laps * modulus) as u128)
+        });
+    one_off.chain(periodic).min()
+}
+fn part2(dirs: &[Dir], map: &Map) -> Option<u128> {
+    let ghosts = map
+        .keys()
+        .filter(|key| key.ends_with('A'))
+        .map(|start| Ghost::trace(start, dirs, map))
+        .collect::<Vec<_>>();
+    first_meeting(&ghosts)
 }
 
 fn main() {
     let input = read_to_string("inputs/day08-input1.txt").unwrap();
     let (dirs, map) = parse(&input).unwrap();
-    let answer = part2(&dirs, &map);
-    println!("answer is: {answer}");
+    match part2(&dirs, &map) {
+        Some(answer) => println!("answer is: {answer}"),
+        None => println!("ghosts never arrive at the same time"),
+    }
 }
 #[cfg(test)]
 mod tests {
@@ -89,6 +157,47 @@ XXX = (XXX, XXX)
     #[test]
     fn part2_test() {
         let (dirs, map) = parse(INPUT1.trim()).unwrap();
-        assert_eq!(part2(&dirs, &map), 6);
+        assert_eq!(part2(&dirs, &map), Some(6));
+    }
+    const INPUT2: &str = r#"
+L
+
+11A = (11B, 11B)
+11B = (11Z, 11Z)
+11Z = (11B, 11B)
+22A = (22Z, 22Z)
+22Z = (22B, 22B)
+22B = (22C, 22C)
+22C = (22Z, 22Z)
+"#;
+    #[test]
+    fn offset_cycles() {
+        let (dirs, map) = parse(INPUT2.trim()).unwrap();
+        let ghost = Ghost::trace("22A", &dirs, &map);
+        assert_eq!(
+            ghost,
+            Ghost {
+                prefix: 1,
+                cycle: 3,
+                prefix_hits: vec![],
+                cycle_hits: vec![1]
+            }
+        );
+        assert_eq!(part2(&dirs, &map), Some(4));
+    }
+    #[test]
+    fn no_meeting() {
+        let (dirs, map) = parse(INPUT2.trim()).unwrap();
+        let even = Ghost::trace("11A", &dirs, &map);
+        let odd = Ghost::trace("11B", &dirs, &map);
+        assert_eq!(first_meeting(&[even, odd]), None);
+        let once = Ghost {
+            prefix: 2,
+            cycle: 1,
+            prefix_hits: vec![1],
+            cycle_hits: vec![],
+        };
+        let ghost = Ghost::trace("22A", &dirs, &map);
+        assert_eq!(first_meeting(&[once, ghost]), Some(1));
     }
 }
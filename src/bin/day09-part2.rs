@@ -1,8 +1,9 @@
 use std::fs::read_to_string;
 
-use itertools::{unfold, Itertools};
+use itertools::Itertools;
+use num::{BigInt, BigRational, One, Zero};
 
-fn parse(input: &str) -> Vec<Vec<i32>> {
+fn parse(input: &str) -> Vec<Vec<i64>> {
     input
         .lines()
         .map(|line| {
@@ -14,36 +15,174 @@ fn parse(input: &str) -> Vec<Vec<i32>> {
         .collect()
 }
 
-fn extrapolate(line: &Vec<i32>) -> i32 {
-    let init = line.to_owned();
-    let sequences: Vec<_> = unfold(init, |v| {
-        *v = v
-            .iter()
-            .tuple_windows()
-            .map(|(l, r)| r - l)
-            .collect::<Vec<_>>();
-        if v.iter().all(|e| *e == 0) {
-            None
-        } else {
-            Some(v.clone())
+#[derive(Debug, PartialEq, Eq)]
+struct Polynomial {
+    differences: Vec<BigInt>,
+    exact: bool,
+}
+impl Polynomial {
+    fn fit(values: &[i64]) -> Self {
+        let mut row = values.iter().map(|&v| BigInt::from(v)).collect_vec();
+        let mut differences = vec![];
+        while !row.is_empty() && !row.iter().all(Zero::is_zero) {
+            differences.push(row[0].clone());
+            row = row.iter().tuple_windows().map(|(l, r)| r - l).collect();
         }
-    })
-    .collect();
-
-    let extrapolated_back = sequences
+        Self {
+            differences,
+            exact: !row.is_empty(),
+        }
+    }
+    fn degree(&self) -> usize {
+        self.differences.len().saturating_sub(1)
+    }
+    fn at(&self, x: &BigRational) -> BigRational {
+        let mut binomial = BigRational::one();
+        let mut sum = BigRational::zero();
+        for (k, difference) in self.differences.iter().enumerate() {
+            sum += &binomial * BigRational::from_integer(difference.clone());
+            binomial = binomial * (x - BigRational::from_integer(k.into()))
+                / BigRational::from_integer((k + 1).into());
+        }
+        sum
+    }
+    fn at_int(&self, x: i64) -> BigInt {
+        self.at(&BigRational::from_integer(x.into())).to_integer()
+    }
+}
+#[derive(Debug, PartialEq, Eq)]
+enum Model {
+    Polynomial(Polynomial),
+    Geometric(BigRational),
+    Periodic(usize),
+    Recurrence(Vec<BigRational>),
+}
+#[derive(Debug, PartialEq, Eq)]
+struct Fit {
+    model: Model,
+    prediction: BigRational,
+    confident: bool,
+}
+fn ratio(value: i64) -> BigRational {
+    BigRational::from_integer(value.into())
+}
+fn geometric(values: &[i64]) -> Option<BigRational> {
+    if values.len() < 3 || values.contains(&0) {
+        return None;
+    }
+    let r = ratio(values[1]) / ratio(values[0]);
+    values
         .iter()
-        .rev()
-        .fold(0, |acc, v| v.first().unwrap() - acc);
-    let first = *line.first().unwrap();
-    first - extrapolated_back
+        .tuple_windows()
+        .all(|(&a, &b)| ratio(a) * &r == ratio(b))
+        .then_some(r)
+}
+fn period(values: &[i64]) -> Option<usize> {
+    (1..=values.len() / 2).find(|&p| (p..values.len()).all(|i| values[i] == values[i - p]))
+}
+fn berlekamp_massey(values: &[i64]) -> Vec<BigRational> {
+    let s = values.iter().map(|&v| ratio(v)).collect_vec();
+    let (mut c, mut b) = (vec![BigRational::one()], vec![BigRational::one()]);
+    let (mut len, mut shift, mut last) = (0, 1, BigRational::one());
+    for n in 0..s.len() {
+        let d = (1..=len).fold(s[n].clone(), |acc, i| acc + &c[i] * &s[n - i]);
+        if d.is_zero() {
+            shift += 1;
+            continue;
+        }
+        let coef = &d / &last;
+        let previous = c.clone();
+        c.resize(c.len().max(b.len() + shift), BigRational::zero());
+        for (i, bi) in b.iter().enumerate() {
+            c[i + shift] -= &coef * bi;
+        }
+        if 2 * len <= n {
+            len = n + 1 - len;
+            b = previous;
+            last = d;
+            shift = 1;
+        } else {
+            shift += 1;
+        }
+    }
+    c.resize(len + 1, BigRational::zero());
+    c.into_iter().skip(1).map(|ci| -ci).collect()
 }
-fn part2(values: &[Vec<i32>]) -> i32 {
-    values.iter().map(extrapolate).sum()
+fn classify(values: &[i64]) -> Fit {
+    let n = values.len();
+    let polynomial = Polynomial::fit(values);
+    if polynomial.exact {
+        let prediction = BigRational::from_integer(polynomial.at_int(n as i64));
+        return Fit {
+            model: Model::Polynomial(polynomial),
+            prediction,
+            confident: true,
+        };
+    }
+    if let Some(r) = geometric(values) {
+        return Fit {
+            prediction: ratio(values[n - 1]) * &r,
+            model: Model::Geometric(r),
+            confident: true,
+        };
+    }
+    if let Some(p) = period(values) {
+        return Fit {
+            prediction: ratio(values[n - p]),
+            model: Model::Periodic(p),
+            confident: true,
+        };
+    }
+    let recurrence = berlekamp_massey(values);
+    if 2 * recurrence.len() < n {
+        let prediction = recurrence
+            .iter()
+            .zip(values.iter().rev())
+            .map(|(c, &v)| c * ratio(v))
+            .sum();
+        return Fit {
+            model: Model::Recurrence(recurrence),
+            prediction,
+            confident: true,
+        };
+    }
+    Fit {
+        prediction: BigRational::from_integer(polynomial.at_int(n as i64)),
+        model: Model::Polynomial(polynomial),
+        confident: false,
+    }
+}
+fn part2(values: &[Vec<i64>]) -> BigRational {
+    values
+        .iter()
+        .map(|line| {
+            let reversed = line.iter().copied().rev().collect_vec();
+            classify(&reversed).prediction
+        })
+        .sum()
 }
 
 fn main() {
     let input = read_to_string("inputs/day09-input1.txt").unwrap();
     let values = parse(&input);
+    if std::env::args().any(|arg| arg == "--models") {
+        for line in &values {
+            let reversed = line.iter().copied().rev().collect_vec();
+            let fit = classify(&reversed);
+            let model = match &fit.model {
+                Model::Polynomial(polynomial) => {
+                    format!("polynomial of degree {}", polynomial.degree())
+                }
+                Model::Geometric(r) => format!("geometric with ratio {r}"),
+                Model::Periodic(p) => format!("periodic with period {p}"),
+                Model::Recurrence(coefficients) => {
+                    format!("recurrence of order {}", coefficients.len())
+                }
+            };
+            let hedge = if fit.confident { "" } else { " (best guess)" };
+            println!("{model}: previous {}{hedge}", fit.prediction);
+        }
+    }
     let answer = part2(&values);
     println!("answer is: {answer}");
 }
@@ -66,6 +205,27 @@ mod tests {
     #[test]
     fn part2_test() {
         let values = parse(INPUT.trim());
-        assert_eq!(part2(&values), 2);
+        assert_eq!(part2(&values), ratio(2));
+    }
+    #[test]
+    fn polynomial_test() {
+        let polynomial = Polynomial::fit(&[10, 13, 16, 21, 30, 45]);
+        assert_eq!((polynomial.degree(), polynomial.exact), (3, true));
+        assert_eq!(polynomial.at_int(-1), 5.into());
+        assert_eq!(polynomial.at_int(6), 68.into());
+        let big = Polynomial::fit(&[i64::MAX, i64::MAX - 1]);
+        assert_eq!(
+            big.at_int(-i64::MAX),
+            BigInt::from(i64::MAX) * BigInt::from(2)
+        );
+    }
+    #[test]
+    fn classify_test() {
+        let values = vec![vec![48, 24, 12, 6], vec![13, 8, 5, 3, 2, 1, 1]];
+        assert_eq!(part2(&values), ratio(96) + ratio(21));
+        let fit = classify(&[6, 12, 24, 48]);
+        assert_eq!(fit.model, Model::Geometric(ratio(2)));
+        let fit = classify(&[2, 3]);
+        assert!(!fit.confident);
     }
 }
@@ -1,21 +1,19 @@
 use std::fmt::Display;
 use std::fs::read_to_string;
 
+use anyhow::anyhow;
 use itertools::Itertools;
-use petgraph::algo::astar;
-use petgraph::stable_graph::NodeIndex;
-use petgraph::{Graph, Undirected};
 
-//#[derive(Debug, PartialEq, Eq, Clone, Copy)]
-//enum CardDir {
-//    N,
-//    S,
-//    E,
-//    W,
-//}
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum CardDir {
+    N,
+    S,
+    E,
+    W,
+}
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 enum Tile {
-    Dir([char; 2]),
+    Dir([CardDir; 2]),
     Ground,
     Start,
 }
@@ -26,12 +24,12 @@ impl Display for Tile {
             "{}",
             match self {
                 Tile::Dir(dir) => match dir {
-                    ['N', 'S'] => '|',
-                    ['W', 'E'] => '-',
-                    ['N', 'E'] => 'L',
-                    ['N', 'W'] => 'J',
-                    ['S', 'W'] => '7',
-                    ['S', 'E'] => 'F',
+                    [CardDir::N, CardDir::S] => '|',
+                    [CardDir::W, CardDir::E] => '-',
+                    [CardDir::N, CardDir::E] => 'L',
+                    [CardDir::N, CardDir::W] => 'J',
+                    [CardDir::S, CardDir::W] => '7',
+                    [CardDir::S, CardDir::E] => 'F',
                     _ => unimplemented!(),
                 },
                 Tile::Ground => '.',
@@ -44,107 +42,124 @@ impl From<char> for Tile {
     fn from(value: char) -> Self {
         use Tile::*;
         match value {
-            '|' => Dir(['N', 'S']),
-            '-' => Dir(['W', 'E']),
-            'L' => Dir(['N', 'E']),
-            'J' => Dir(['N', 'W']),
-            '7' => Dir(['S', 'W']),
-            'F' => Dir(['S', 'E']),
+            '|' => Dir([CardDir::N, CardDir::S]),
+            '-' => Dir([CardDir::W, CardDir::E]),
+            'L' => Dir([CardDir::N, CardDir::E]),
+            'J' => Dir([CardDir::N, CardDir::W]),
+            '7' => Dir([CardDir::S, CardDir::W]),
+            'F' => Dir([CardDir::S, CardDir::E]),
             '.' => Ground,
             'S' => Start,
             _ => unimplemented!("{value}"),
         }
     }
 }
-impl Tile {
-    fn contains(&self, ch: char) -> bool {
-        if let Tile::Dir(dir) = self {
-            dir.contains(&ch)
-        } else {
-            false
-        }
-    }
-}
-
 fn parse(input: &str) -> Vec<Vec<Tile>> {
     input
         .lines()
         .map(|line| line.chars().map(Tile::from).collect())
         .collect()
 }
-fn construct_graph(map: &Vec<Vec<Tile>>) -> Graph<(Tile, (usize, usize)), (), Undirected> {
-    let mut graph = Graph::new_undirected();
-    let w = map[0].len();
-    let h = map.len();
+const SHAPES: [[CardDir; 2]; 6] = [
+    [CardDir::N, CardDir::S],
+    [CardDir::W, CardDir::E],
+    [CardDir::N, CardDir::E],
+    [CardDir::N, CardDir::W],
+    [CardDir::S, CardDir::W],
+    [CardDir::S, CardDir::E],
+];
 
-    (0..h).cartesian_product(0..w).for_each(|(r, c)| {
-        graph.add_node((map[r][c], (r, c)));
-    });
-
-    (0..h).cartesian_product(0..w).for_each(|(r, c)| {
-        if c + 1 < w && map[r][c].contains('E') && map[r][c + 1].contains('W') {
-            graph.add_edge(NodeIndex::new(r * w + c), NodeIndex::new(r * w + c + 1), ());
-        }
-        if r + 1 < h && map[r][c].contains('S') && map[r + 1][c].contains('N') {
-            graph.add_edge(
-                NodeIndex::new(r * w + c),
-                NodeIndex::new((r + 1) * w + c),
-                (),
-            );
-        }
-    });
-    graph
+fn opposite(dir: CardDir) -> CardDir {
+    match dir {
+        CardDir::N => CardDir::S,
+        CardDir::S => CardDir::N,
+        CardDir::E => CardDir::W,
+        CardDir::W => CardDir::E,
+    }
 }
-fn start_pos(pipes: &Vec<Vec<Tile>>) -> [(usize, usize); 3] {
-    let (start_r, start_c) = (0..pipes.len())
-        .cartesian_product(0..pipes[0].len())
-        .filter(|(r, c)| pipes[*r][*c] == Tile::Start)
-        .last()
-        .unwrap();
-    let mut v = vec![(start_r, start_c)];
-    if start_r > 0 && pipes[start_r - 1][start_c].contains('S') {
-        v.push((start_r - 1, start_c))
+fn neighbour((r, c): (usize, usize), dir: CardDir, h: usize, w: usize) -> Option<(usize, usize)> {
+    match dir {
+        CardDir::N => r.checked_sub(1).map(|r| (r, c)),
+        CardDir::S => (r + 1 < h).then_some((r + 1, c)),
+        CardDir::W => c.checked_sub(1).map(|c| (r, c)),
+        CardDir::E => (c + 1 < w).then_some((r, c + 1)),
     }
-    if pipes[start_r + 1][start_c].contains('N') {
-        v.push((start_r + 1, start_c))
+}
+fn start_pos(pipes: &[Vec<Tile>]) -> anyhow::Result<(usize, usize)> {
+    if pipes.iter().all(Vec::is_empty) {
+        return Err(anyhow!("empty grid"));
     }
-    if start_c > 0 && pipes[start_r][start_c - 1].contains('E') {
-        v.push((start_r, start_c - 1))
+    let starts = pipes
+        .iter()
+        .enumerate()
+        .flat_map(|(r, row)| {
+            row.iter()
+                .positions(|&t| t == Tile::Start)
+                .map(move |c| (r, c))
+        })
+        .collect_vec();
+    match starts.as_slice() {
+        [start] => Ok(*start),
+        [] => Err(anyhow!("no start tile")),
+        _ => Err(anyhow!("several start tiles at {starts:?}")),
     }
-    if pipes[start_r][start_c + 1].contains('W') {
-        v.push((start_r, start_c + 1))
+}
+fn follow(
+    pipes: &[Vec<Tile>],
+    start: (usize, usize),
+    shape: [CardDir; 2],
+) -> Option<Vec<(usize, usize)>> {
+    let (h, w) = (pipes.len(), pipes[0].len());
+    let mut path = vec![start];
+    let (mut pos, mut dir) = (start, shape[0]);
+    while path.len() <= h * w {
+        pos = neighbour(pos, dir, h, w)?;
+        let from = opposite(dir);
+        if pos == start {
+            return (from == shape[1]).then_some(path);
+        }
+        let Tile::Dir(ends) = pipes[pos.0][pos.1] else {
+            return None;
+        };
+        dir = match ends {
+            [a, b] if a == from => b,
+            [a, b] if b == from => a,
+            _ => return None,
+        };
+        path.push(pos);
     }
-    v.try_into().expect("Starting point and 2 connected points")
+    None
 }
-fn print_pipes(pipes: &Vec<Vec<Tile>>) {
-    for row in pipes {
-        for t in row {
-            print!("{t}");
+fn find_loop(pipes: &mut [Vec<Tile>]) -> anyhow::Result<Vec<(usize, usize)>> {
+    let start = start_pos(pipes)?;
+    let mut loops = SHAPES
+        .iter()
+        .filter_map(|&shape| follow(pipes, start, shape).map(|path| (shape, path)))
+        .collect_vec();
+    match loops.len() {
+        0 => Err(anyhow!("start tile at {start:?} is not on a closed loop")),
+        1 => {
+            let (shape, path) = loops.remove(0);
+            pipes[start.0][start.1] = Tile::Dir(shape);
+            Ok(path)
         }
-        println!();
+        _ => Err(anyhow!(
+            "start tile at {start:?} is ambiguous, it closes a loop as any of {}",
+            loops
+                .iter()
+                .map(|(shape, path)| format!("{} (length {})", Tile::Dir(*shape), path.len()))
+                .join(", ")
+        )),
     }
 }
-
-fn part1(pipes: Vec<Vec<Tile>>) -> usize {
-    let [start, begin, end] = start_pos(&pipes);
-    let w = pipes[0].len();
-    let graph = construct_graph(&pipes);
-    let (_cost, mut loop_path) = astar(
-        &graph,
-        NodeIndex::new(begin.0 * w + begin.1),
-        |finish| finish == NodeIndex::new(end.0 * w + end.1),
-        |_| 0,
-        |_| 0,
-    )
-    .unwrap();
-    loop_path.push(NodeIndex::new(start.0 * w + start.1));
-    loop_path.len().div_ceil(2)
+fn part1(mut pipes: Vec<Vec<Tile>>) -> anyhow::Result<usize> {
+    Ok(find_loop(&mut pipes)?.len().div_ceil(2))
 }
 
 fn main() {
     let input = read_to_string("inputs/day10-input1.txt").unwrap();
     let pipes = parse(&input);
-    let answer = part1(pipes);
+    let answer = part1(pipes).unwrap();
     println!("answer is: {answer}");
 }
 #[cfg(test)]
@@ -168,18 +183,35 @@ LJ...
 "#;
     #[test]
     fn tile_display() {
-        let tile = Tile::Dir(['N', 'S']);
+        let tile = Tile::Dir([CardDir::N, CardDir::S]);
         println!("{tile}");
         assert_eq!(format!("{tile}"), "|");
     }
     #[test]
-    fn part1_test1() {
+    fn part1_test1() -> anyhow::Result<()> {
         let pipes = parse(INPUT1.trim());
-        assert_eq!(part1(pipes), 4);
+        assert_eq!(part1(pipes)?, 4);
+        Ok(())
     }
     #[test]
-    fn part1_test2() {
+    fn part1_test2() -> anyhow::Result<()> {
         let pipes = parse(INPUT2.trim());
-        assert_eq!(part1(pipes), 8);
+        assert_eq!(part1(pipes)?, 8);
+        Ok(())
+    }
+    #[test]
+    fn start_errors() {
+        let open = parse("S-7\n|.|\nL-.");
+        assert!(part1(open)
+            .unwrap_err()
+            .to_string()
+            .contains("not on a closed loop"));
+        let single = parse("F7F7\n|LS|\nL--J");
+        assert_eq!(part1(single).unwrap(), 6);
+        let ambiguous = parse("F-7.\n|FS7\nLJLJ");
+        let error = part1(ambiguous).unwrap_err().to_string();
+        assert!(error.contains("ambiguous"), "{error}");
+        assert!(part1(parse(".S.\n.S.")).is_err());
+        assert_eq!(part1(parse("")).unwrap_err().to_string(), "empty grid");
     }
 }
@@ -1,25 +1,25 @@
+use std::collections::{HashSet, VecDeque};
 use std::fmt::Display;
 use std::fs::read_to_string;
 
+use anyhow::anyhow;
 use itertools::Itertools;
-use petgraph::algo::astar;
-use petgraph::stable_graph::NodeIndex;
-use petgraph::{Graph, Undirected};
 
-//#[derive(Debug, PartialEq, Eq, Clone, Copy)]
-//enum CardDir {
-//    N,
-//    S,
-//    E,
-//    W,
-//}
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum CardDir {
+    N,
+    S,
+    E,
+    W,
+}
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 enum Tile {
-    Dir([char; 2]),
+    Dir([CardDir; 2]),
     Ground,
     Path,
     Outside,
     Inside,
+    Enclosed,
     Start,
 }
 impl Display for Tile {
@@ -29,18 +29,19 @@ impl Display for Tile {
             "{}",
             match self {
                 Tile::Dir(dir) => match dir {
-                    ['N', 'S'] => '|',
-                    ['W', 'E'] => '-',
-                    ['N', 'E'] => 'L',
-                    ['N', 'W'] => 'J',
-                    ['S', 'W'] => '7',
-                    ['S', 'E'] => 'F',
+                    [CardDir::N, CardDir::S] => '|',
+                    [CardDir::W, CardDir::E] => '-',
+                    [CardDir::N, CardDir::E] => 'L',
+                    [CardDir::N, CardDir::W] => 'J',
+                    [CardDir::S, CardDir::W] => '7',
+                    [CardDir::S, CardDir::E] => 'F',
                     _ => unimplemented!(),
                 },
                 Tile::Path => '#',
                 Tile::Ground => '.',
                 Tile::Outside => 'O',
                 Tile::Inside => 'I',
+                Tile::Enclosed => 'X',
                 Tile::Start => 'S',
             }
         )
@@ -50,12 +51,12 @@ impl From<char> for Tile {
     fn from(value: char) -> Self {
         use Tile::*;
         match value {
-            '|' => Dir(['N', 'S']),
-            '-' => Dir(['W', 'E']),
-            'L' => Dir(['N', 'E']),
-            'J' => Dir(['N', 'W']),
-            '7' => Dir(['S', 'W']),
-            'F' => Dir(['S', 'E']),
+            '|' => Dir([CardDir::N, CardDir::S]),
+            '-' => Dir([CardDir::W, CardDir::E]),
+            'L' => Dir([CardDir::N, CardDir::E]),
+            'J' => Dir([CardDir::N, CardDir::W]),
+            '7' => Dir([CardDir::S, CardDir::W]),
+            'F' => Dir([CardDir::S, CardDir::E]),
             '.' => Ground,
             'S' => Start,
             _ => unimplemented!("{value}"),
@@ -63,80 +64,114 @@ impl From<char> for Tile {
     }
 }
 impl Tile {
-    fn contains(&self, ch: char) -> bool {
-        if let Tile::Dir(dir) = self {
-            dir.contains(&ch)
+    fn contains(&self, dir: CardDir) -> bool {
+        if let Tile::Dir(ends) = self {
+            ends.contains(&dir)
         } else {
             false
         }
     }
 }
 
-fn replace_start_tile(begin: (usize, usize), end: (usize, usize)) -> Tile {
-    let diff_y = begin.0 as isize - end.0 as isize;
-    let diff_x = begin.1 as isize - end.1 as isize;
-    match (diff_x, diff_y) {
-        (0, 2) | (0, -2) => Tile::Dir(['W', 'E']),
-        (2, 0) | (-2, 0) => Tile::Dir(['N', 'S']),
-        (1, 1) => Tile::Dir(['S', 'W']),
-        (-1, -1) => Tile::Dir(['N', 'E']),
-        (-1, 1) => Tile::Dir(['S', 'E']),
-        (1, -1) => Tile::Dir(['N', 'W']),
-        _ => todo!(),
-    }
-}
-
 fn parse(input: &str) -> Vec<Vec<Tile>> {
     input
         .lines()
         .map(|line| line.chars().map(Tile::from).collect())
         .collect()
 }
-fn construct_graph(map: &Vec<Vec<Tile>>) -> Graph<(Tile, (usize, usize)), (), Undirected> {
-    let mut graph = Graph::new_undirected();
-    let w = map[0].len();
-    let h = map.len();
-
-    (0..h).cartesian_product(0..w).for_each(|(r, c)| {
-        graph.add_node((map[r][c], (r, c)));
-    });
+const SHAPES: [[CardDir; 2]; 6] = [
+    [CardDir::N, CardDir::S],
+    [CardDir::W, CardDir::E],
+    [CardDir::N, CardDir::E],
+    [CardDir::N, CardDir::W],
+    [CardDir::S, CardDir::W],
+    [CardDir::S, CardDir::E],
+];
 
-    (0..h).cartesian_product(0..w).for_each(|(r, c)| {
-        if c + 1 < w && map[r][c].contains('E') && map[r][c + 1].contains('W') {
-            graph.add_edge(NodeIndex::new(r * w + c), NodeIndex::new(r * w + c + 1), ());
-        }
-        if r + 1 < h && map[r][c].contains('S') && map[r + 1][c].contains('N') {
-            graph.add_edge(
-                NodeIndex::new(r * w + c),
-                NodeIndex::new((r + 1) * w + c),
-                (),
-            );
-        }
-    });
-    graph
+fn opposite(dir: CardDir) -> CardDir {
+    match dir {
+        CardDir::N => CardDir::S,
+        CardDir::S => CardDir::N,
+        CardDir::E => CardDir::W,
+        CardDir::W => CardDir::E,
+    }
+}
+fn neighbour((r, c): (usize, usize), dir: CardDir, h: usize, w: usize) -> Option<(usize, usize)> {
+    match dir {
+        CardDir::N => r.checked_sub(1).map(|r| (r, c)),
+        CardDir::S => (r + 1 < h).then_some((r + 1, c)),
+        CardDir::W => c.checked_sub(1).map(|c| (r, c)),
+        CardDir::E => (c + 1 < w).then_some((r, c + 1)),
+    }
 }
-fn start_pos(pipes: &Vec<Vec<Tile>>) -> [(usize, usize); 3] {
-    let (start_r, start_c) = (0..pipes.len())
-        .cartesian_product(0..pipes[0].len())
-        .filter(|(r, c)| pipes[*r][*c] == Tile::Start)
-        .last()
-        .unwrap();
-    let mut v = vec![(start_r, start_c)];
-    if start_r > 0 && pipes[start_r - 1][start_c].contains('S') {
-        v.push((start_r - 1, start_c))
+fn start_pos(pipes: &[Vec<Tile>]) -> anyhow::Result<(usize, usize)> {
+    if pipes.iter().all(Vec::is_empty) {
+        return Err(anyhow!("empty grid"));
     }
-    if pipes[start_r + 1][start_c].contains('N') {
-        v.push((start_r + 1, start_c))
+    let starts = pipes
+        .iter()
+        .enumerate()
+        .flat_map(|(r, row)| {
+            row.iter()
+                .positions(|&t| t == Tile::Start)
+                .map(move |c| (r, c))
+        })
+        .collect_vec();
+    match starts.as_slice() {
+        [start] => Ok(*start),
+        [] => Err(anyhow!("no start tile")),
+        _ => Err(anyhow!("several start tiles at {starts:?}")),
     }
-    if start_c > 0 && pipes[start_r][start_c - 1].contains('E') {
-        v.push((start_r, start_c - 1))
+}
+fn follow(
+    pipes: &[Vec<Tile>],
+    start: (usize, usize),
+    shape: [CardDir; 2],
+) -> Option<Vec<(usize, usize)>> {
+    let (h, w) = (pipes.len(), pipes[0].len());
+    let mut path = vec![start];
+    let (mut pos, mut dir) = (start, shape[0]);
+    while path.len() <= h * w {
+        pos = neighbour(pos, dir, h, w)?;
+        let from = opposite(dir);
+        if pos == start {
+            return (from == shape[1]).then_some(path);
+        }
+        let Tile::Dir(ends) = pipes[pos.0][pos.1] else {
+            return None;
+        };
+        dir = match ends {
+            [a, b] if a == from => b,
+            [a, b] if b == from => a,
+            _ => return None,
+        };
+        path.push(pos);
     }
-    if pipes[start_r][start_c + 1].contains('W') {
-        v.push((start_r, start_c + 1))
+    None
+}
+fn find_loop(pipes: &mut [Vec<Tile>]) -> anyhow::Result<Vec<(usize, usize)>> {
+    let start = start_pos(pipes)?;
+    let mut loops = SHAPES
+        .iter()
+        .filter_map(|&shape| follow(pipes, start, shape).map(|path| (shape, path)))
+        .collect_vec();
+    match loops.len() {
+        0 => Err(anyhow!("start tile at {start:?} is not on a closed loop")),
+        1 => {
+            let (shape, path) = loops.remove(0);
+            pipes[start.0][start.1] = Tile::Dir(shape);
+            Ok(path)
+        }
+        _ => Err(anyhow!(
+            "start tile at {start:?} is ambiguous, it closes a loop as any of {}",
+            loops
+                .iter()
+                .map(|(shape, path)| format!("{} (length {})", Tile::Dir(*shape), path.len()))
+                .join(", ")
+        )),
     }
-    v.try_into().expect("Starting point and 2 connected points")
 }
-fn print_pipes(pipes: &Vec<Vec<Tile>>) {
+fn print_pipes(pipes: &[Vec<Tile>]) {
     for row in pipes {
         for t in row {
             print!("{t}");
@@ -145,67 +180,114 @@ fn print_pipes(pipes: &Vec<Vec<Tile>>) {
     }
 }
 
-fn part2(mut pipes: Vec<Vec<Tile>>) -> usize {
-    let [start, begin, end] = start_pos(&pipes);
-    let h = pipes.len();
-    let w = pipes[0].len();
-    let graph = construct_graph(&pipes);
-    let (_cost, mut loop_path) = astar(
-        &graph,
-        NodeIndex::new(begin.0 * w + begin.1),
-        |finish| finish == NodeIndex::new(end.0 * w + end.1),
-        |_| 0,
-        |_| 0,
-    )
-    .unwrap();
-    let start_tile = replace_start_tile(begin, end);
-    pipes[start.0][start.1] = start_tile;
-    print_pipes(&pipes);
-    loop_path.push(NodeIndex::new(start.0 * w + start.1));
-    let loop_coords: Vec<_> = loop_path
+fn shoelace_interior(loop_coords: &[(usize, usize)]) -> usize {
+    let double_area = loop_coords
         .iter()
-        .map(|node| (node.index() / w, node.index() % w))
-        .collect();
-    let crossings = (0..h)
+        .circular_tuple_windows()
+        .map(|(&(y1, x1), &(y2, x2))| (x1 * y2) as isize - (x2 * y1) as isize)
+        .sum::<isize>()
+        .unsigned_abs();
+    (double_area + 2 - loop_coords.len()) / 2
+}
+fn scanline_interior(pipes: &mut [Vec<Tile>], loop_coords: &HashSet<(usize, usize)>) -> usize {
+    let mut count = 0;
+    for (r, row) in pipes.iter_mut().enumerate() {
+        let mut inside = false;
+        for (c, tile) in row.iter_mut().enumerate() {
+            if loop_coords.contains(&(r, c)) {
+                inside ^= tile.contains(CardDir::S);
+                *tile = Tile::Path;
+            } else if inside {
+                *tile = Tile::Inside;
+                count += 1;
+            } else {
+                *tile = Tile::Outside;
+            }
+        }
+    }
+    count
+}
+
+fn flood_enclosed(
+    pipes: &[Vec<Tile>],
+    loop_coords: &HashSet<(usize, usize)>,
+) -> HashSet<(usize, usize)> {
+    let (h, w) = (pipes.len(), pipes[0].len());
+    let (dh, dw) = (2 * h + 1, 2 * w + 1);
+    let mut wall = vec![vec![false; dw]; dh];
+    for (r, c) in (0..h).cartesian_product(0..w) {
+        let tile = pipes[r][c];
+        if let Tile::Dir(_) = tile {
+            wall[2 * r + 1][2 * c + 1] = true;
+            if c + 1 < w && tile.contains(CardDir::E) && pipes[r][c + 1].contains(CardDir::W) {
+                wall[2 * r + 1][2 * c + 2] = true;
+            }
+            if r + 1 < h && tile.contains(CardDir::S) && pipes[r + 1][c].contains(CardDir::N) {
+                wall[2 * r + 2][2 * c + 1] = true;
+            }
+        }
+    }
+    let mut reached = vec![vec![false; dw]; dh];
+    let mut queue = VecDeque::from([(0_usize, 0_usize)]);
+    reached[0][0] = true;
+    while let Some((y, x)) = queue.pop_front() {
+        let next = [
+            (y.wrapping_sub(1), x),
+            (y + 1, x),
+            (y, x.wrapping_sub(1)),
+            (y, x + 1),
+        ];
+        for (ny, nx) in next {
+            if ny < dh && nx < dw && !wall[ny][nx] && !reached[ny][nx] {
+                reached[ny][nx] = true;
+                queue.push_back((ny, nx));
+            }
+        }
+    }
+    (0..h)
         .cartesian_product(0..w)
         .filter(|coord| !loop_coords.contains(coord))
-        .map(|(r, c)| {
-            let crossing = loop_coords
-                .iter()
-                .filter(move |(y, _)| *y == r)
-                .filter(|(y, x)| {
-                    !matches!(
-                        pipes[*y][*x],
-                        Tile::Dir(['N', 'E']) | Tile::Dir(['N', 'W']) | Tile::Dir(['W', 'E'])
-                    )
-                })
-                .filter(|(_, x)| *x < c)
-                .count();
-            ((r, c), crossing)
+        .filter(|&(r, c)| {
+            (2 * r..=2 * r + 2)
+                .cartesian_product(2 * c..=2 * c + 2)
+                .all(|(y, x)| !reached[y][x])
         })
-        .collect::<Vec<_>>();
-
-    for (r, c) in loop_coords {
-        pipes[r][c] = Tile::Path;
-    }
-    for ((r, c), crossing) in crossings.iter() {
-        if crossing % 2 == 0 {
-            pipes[*r][*c] = Tile::Outside;
-        } else {
-            pipes[*r][*c] = Tile::Inside;
+        .collect()
+}
+fn squeeze(mut pipes: Vec<Vec<Tile>>) -> anyhow::Result<(usize, usize)> {
+    let loop_coords = find_loop(&mut pipes)?.into_iter().collect();
+    let enclosed = flood_enclosed(&pipes, &loop_coords);
+    let parity = scanline_interior(&mut pipes, &loop_coords);
+    for &(r, c) in &enclosed {
+        if pipes[r][c] == Tile::Outside {
+            pipes[r][c] = Tile::Enclosed;
         }
     }
     print_pipes(&pipes);
-    crossings
-        .iter()
-        .filter(|(_, crossing)| crossing % 2 > 0)
-        .count()
+    Ok((parity, enclosed.len()))
+}
+
+fn part2(mut pipes: Vec<Vec<Tile>>) -> anyhow::Result<usize> {
+    let loop_coords = find_loop(&mut pipes)?;
+    let inside = shoelace_interior(&loop_coords);
+    let parity = scanline_interior(&mut pipes, &loop_coords.iter().copied().collect());
+    if inside != parity {
+        return Err(anyhow!(
+            "shoelace counts {inside} inside tiles but scanline counts {parity}"
+        ));
+    }
+    print_pipes(&pipes);
+    Ok(inside)
 }
 
 fn main() {
     let input = read_to_string("inputs/day10-input1.txt").unwrap();
     let pipes = parse(&input);
-    let answer = part2(pipes);
+    if std::env::args().any(|arg| arg == "--squeeze") {
+        let (parity, flood) = squeeze(pipes.clone()).unwrap();
+        println!("parity inside: {parity}, flood-fill enclosed: {flood}");
+    }
+    let answer = part2(pipes).unwrap();
     println!("answer is: {answer}");
 }
 #[cfg(test)]
@@ -256,23 +338,66 @@ L7JLJL-JLJLJL--JLJ.L
     // ....LJ...
     #[test]
     fn tile_display() {
-        let tile = Tile::Dir(['N', 'S']);
+        let tile = Tile::Dir([CardDir::N, CardDir::S]);
         println!("{tile}");
         assert_eq!(format!("{tile}"), "|");
     }
     #[test]
-    fn part2_test1() {
+    fn interior_test() {
+        let mut pipes = parse(INPUT2.trim());
+        let loop_coords = find_loop(&mut pipes).unwrap();
+        assert_eq!(loop_coords.len(), 140);
+        assert_eq!(shoelace_interior(&loop_coords), 8);
+        let loop_set = loop_coords.iter().copied().collect();
+        assert_eq!(scanline_interior(&mut pipes, &loop_set), 8);
+        let marked = pipes.iter().flatten().filter(|&&t| t == Tile::Inside);
+        assert_eq!(marked.count(), 8);
+    }
+    const INPUT4: &str = r#"
+..........F-7
+.S------7.|.|
+.|F----7|.L-J
+.||....||....
+.||....||....
+.|L-7F-J|....
+.|..||..|....
+.L--JL--J....
+.............
+"#;
+    #[test]
+    fn squeeze_test() -> anyhow::Result<()> {
+        assert_eq!(squeeze(parse(INPUT1.trim()))?, (4, 4));
+        assert_eq!(squeeze(parse(INPUT2.trim()))?, (8, 8));
+        assert_eq!(squeeze(parse(INPUT4.trim()))?, (4, 5));
+        let mut pipes = parse(INPUT4.trim());
+        let loop_coords = find_loop(&mut pipes)?.into_iter().collect();
+        let enclosed = flood_enclosed(&pipes, &loop_coords);
+        assert!(enclosed.contains(&(1, 11)));
+        assert!(!enclosed.contains(&(0, 10)));
+        Ok(())
+    }
+    #[test]
+    fn part2_test1() -> anyhow::Result<()> {
         let pipes = parse(INPUT1.trim());
-        assert_eq!(part2(pipes), 4);
+        assert_eq!(part2(pipes)?, 4);
+        Ok(())
     }
     #[test]
-    fn part2_test2() {
+    fn part2_test2() -> anyhow::Result<()> {
         let pipes = parse(INPUT2.trim());
-        assert_eq!(part2(pipes), 8);
+        assert_eq!(part2(pipes)?, 8);
+        Ok(())
     }
     #[test]
-    fn part2_test3() {
+    fn part2_test3() -> anyhow::Result<()> {
         let pipes = parse(INPUT3.trim());
-        assert_eq!(part2(pipes), 10);
+        assert_eq!(part2(pipes)?, 10);
+        Ok(())
+    }
+    #[test]
+    fn empty_interior() -> anyhow::Result<()> {
+        assert_eq!(part2(parse("S7\nLJ"))?, 0);
+        assert_eq!(part2(parse("S--7\nL--J"))?, 0);
+        Ok(())
     }
 }
@@ -1,8 +1,10 @@
+use std::collections::BTreeMap;
 use std::fmt::Display;
 use std::fs::read_to_string;
 
 use glam::IVec2;
-use itertools::Itertools;
+use num::rational::Ratio;
+use num::{One, Zero};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Tile {
@@ -37,12 +39,16 @@ struct GalaxyMap {
     galaxies: Vec<IVec2>,
     width: usize,
     height: usize,
+    row_factor: Ratio<u128>,
+    col_factor: Ratio<u128>,
+    xs: Vec<Ratio<u128>>,
+    ys: Vec<Ratio<u128>>,
 }
 impl GalaxyMap {
     fn new(tiles: Vec<Vec<Tile>>) -> Self {
         let width = tiles[0].len();
         let height = tiles.len();
-        Self {
+        let mut galaxy_map = Self {
             galaxies: tiles
                 .iter()
                 .enumerate()
@@ -55,7 +61,13 @@ impl GalaxyMap {
                 .collect(),
             width,
             height,
-        }
+            row_factor: Ratio::one(),
+            col_factor: Ratio::one(),
+            xs: vec![],
+            ys: vec![],
+        };
+        galaxy_map.reindex();
+        galaxy_map
     }
     fn get(&self, x: usize, y: usize) -> Tile {
         let coord = IVec2::from((x as i32, y as i32));
@@ -65,51 +77,198 @@ impl GalaxyMap {
             Tile::Empty
         }
     }
-    fn expand(&mut self, expand_value: i32) {
-        let empty_rows: Vec<i32> = (0..self.height as i32)
-            .filter(|y| {
-                self.galaxies
-                    .iter()
-                    .map(|coord| coord.y)
-                    .all(|coord_y| coord_y != *y)
-            })
-            .collect();
-        let empty_cols: Vec<i32> = (0..self.width as i32)
-            .filter(|x| {
-                self.galaxies
-                    .iter()
-                    .map(|coord| coord.x)
-                    .all(|coord_x| coord_x != *x)
-            })
+    fn expand(&mut self, row_factor: Ratio<u128>, col_factor: Ratio<u128>) {
+        self.row_factor = row_factor;
+        self.col_factor = col_factor;
+        self.reindex();
+    }
+    fn reindex(&mut self) {
+        self.xs = Self::offsets(&self.occupied(self.width, |c| c.x), &self.col_factor);
+        self.ys = Self::offsets(&self.occupied(self.height, |c| c.y), &self.row_factor);
+    }
+    fn position(&self, galaxy_id: usize) -> (Ratio<u128>, Ratio<u128>) {
+        let coord = self.galaxies[galaxy_id];
+        (self.xs[coord.x as usize], self.ys[coord.y as usize])
+    }
+    fn occupied(&self, len: usize, axis: impl Fn(&IVec2) -> i32) -> Vec<bool> {
+        let mut occupied = vec![false; len];
+        self.galaxies
+            .iter()
+            .for_each(|coord| occupied[axis(coord) as usize] = true);
+        occupied
+    }
+    fn offsets(occupied: &[bool], factor: &Ratio<u128>) -> Vec<Ratio<u128>> {
+        let mut offsets = Vec::with_capacity(occupied.len());
+        let mut offset = Ratio::zero();
+        for &filled in occupied {
+            offsets.push(offset);
+            offset += if filled { Ratio::one() } else { *factor };
+        }
+        offsets
+    }
+    fn positions(&self) -> Vec<(Ratio<u128>, Ratio<u128>)> {
+        (0..self.galaxies.len())
+            .map(|id| self.position(id))
+            .collect()
+    }
+    fn length(&self, galaxy_id1: usize, galaxy_id2: usize) -> Ratio<u128> {
+        let (x1, y1) = self.position(galaxy_id1);
+        let (x2, y2) = self.position(galaxy_id2);
+        x1.max(x2) - x1.min(x2) + y1.max(y2) - y1.min(y2)
+    }
+    fn axis_sum(mut coords: Vec<Ratio<u128>>) -> Ratio<u128> {
+        coords.sort();
+        let mut prefix = Ratio::zero();
+        let mut sum = Ratio::zero();
+        for (i, coord) in coords.into_iter().enumerate() {
+            sum += coord * i as u128 - prefix;
+            prefix += coord;
+        }
+        sum
+    }
+    fn distance_sum(&self) -> Ratio<u128> {
+        let (xs, ys) = self.positions().into_iter().unzip();
+        Self::axis_sum(xs) + Self::axis_sum(ys)
+    }
+    fn index(&self) -> StarIndex {
+        StarIndex::new(self.positions())
+    }
+}
+#[derive(Debug)]
+struct Axis {
+    sorted: Vec<Ratio<u128>>,
+    prefix: Vec<Ratio<u128>>,
+}
+impl Axis {
+    fn new(mut sorted: Vec<Ratio<u128>>) -> Self {
+        sorted.sort();
+        let prefix = std::iter::once(Ratio::zero())
+            .chain(sorted.iter().scan(Ratio::zero(), |acc, &coord| {
+                *acc += coord;
+                Some(*acc)
+            }))
             .collect();
-        for y in empty_rows.into_iter().rev() {
-            self.galaxies
-                .iter_mut()
-                .filter(|coord| coord.y > y)
-                .for_each(|coord| coord.y += expand_value - 1);
-            self.height += 1;
+        Self { sorted, prefix }
+    }
+    fn distance_sum(&self, coord: Ratio<u128>) -> Ratio<u128> {
+        let below = self.sorted.partition_point(|&c| c < coord);
+        let total = self.prefix[self.sorted.len()];
+        let lower = coord * below as u128 - self.prefix[below];
+        let upper = total - self.prefix[below] - coord * (self.sorted.len() - below) as u128;
+        lower + upper
+    }
+}
+
+#[derive(Debug)]
+struct StarIndex {
+    positions: Vec<(Ratio<u128>, Ratio<u128>)>,
+    by_x: Vec<usize>,
+    rank: Vec<usize>,
+    xs: Axis,
+    ys: Axis,
+}
+impl StarIndex {
+    fn new(positions: Vec<(Ratio<u128>, Ratio<u128>)>) -> Self {
+        let mut by_x = (0..positions.len()).collect::<Vec<_>>();
+        by_x.sort_by_key(|&id| positions[id]);
+        let mut rank = vec![0; positions.len()];
+        by_x.iter().enumerate().for_each(|(r, &id)| rank[id] = r);
+        let (xs, ys) = positions.iter().copied().unzip();
+        Self {
+            by_x,
+            rank,
+            xs: Axis::new(xs),
+            ys: Axis::new(ys),
+            positions,
         }
-        for x in empty_cols.into_iter().rev() {
-            self.galaxies
-                .iter_mut()
-                .filter(|coord| coord.x > x)
-                .for_each(|coord| coord.x += expand_value - 1);
-            self.width += 1;
+    }
+    fn distance(&self, id1: usize, id2: usize) -> Ratio<u128> {
+        let (x1, y1) = self.positions[id1];
+        let (x2, y2) = self.positions[id2];
+        x1.max(x2) - x1.min(x2) + y1.max(y2) - y1.min(y2)
+    }
+    fn nearest(&self, id: usize) -> Option<(usize, Ratio<u128>)> {
+        let (x, _) = self.positions[id];
+        let rank = self.rank[id];
+        let mut best: Option<(usize, Ratio<u128>)> = None;
+        let mut left = self.by_x[..rank].iter().rev().peekable();
+        let mut right = self.by_x[rank + 1..].iter().peekable();
+        loop {
+            let gap = |other: usize| {
+                let ox = self.positions[other].0;
+                ox.max(x) - ox.min(x)
+            };
+            let candidate = match (left.peek(), right.peek()) {
+                (Some(&&l), Some(&&r)) if gap(l) <= gap(r) => left.next(),
+                (Some(_), Some(_)) => right.next(),
+                (Some(_), None) => left.next(),
+                (None, Some(_)) => right.next(),
+                (None, None) => break,
+            };
+            let other = *candidate.unwrap();
+            if best.is_some_and(|(_, d)| gap(other) > d) {
+                break;
+            }
+            let d = self.distance(id, other);
+            if best.is_none_or(|(b, bd)| (d, other) < (bd, b)) {
+                best = Some((other, d));
+            }
         }
+        best
+    }
+    fn nearest_all(&self) -> Vec<Option<(usize, Ratio<u128>)>> {
+        (0..self.positions.len())
+            .map(|id| self.nearest(id))
+            .collect()
     }
-    fn length(&self, galaxy_id1: usize, galaxy_id2: usize) -> i32 {
-        let coord1 = self.galaxies[galaxy_id1];
-        let coord2 = self.galaxies[galaxy_id2];
-        (coord1.x - coord2.x).abs() + (coord1.y - coord2.y).abs()
+    fn farthest_pair(&self) -> Option<(usize, usize, Ratio<u128>)> {
+        let max_y = self.ys.sorted.last()?;
+        let extremes = |key: &dyn Fn(usize) -> Ratio<u128>| {
+            let ids = 0..self.positions.len();
+            let lo = ids.clone().min_by_key(|&id| key(id)).unwrap();
+            let hi = ids.max_by_key(|&id| key(id)).unwrap();
+            (lo, hi, key(hi) - key(lo))
+        };
+        let sum = extremes(&|id| self.positions[id].0 + self.positions[id].1);
+        let diff = extremes(&|id| self.positions[id].0 + max_y - self.positions[id].1);
+        let (a, b, d) = if diff.2 > sum.2 { diff } else { sum };
+        (a != b).then_some((a.min(b), a.max(b), d))
+    }
+    fn histogram(&self) -> BTreeMap<Ratio<u128>, usize> {
+        let mut histogram = BTreeMap::new();
+        for a in 0..self.positions.len() {
+            for b in a + 1..self.positions.len() {
+                *histogram.entry(self.distance(a, b)).or_insert(0) += 1;
+            }
+        }
+        histogram
+    }
+    fn distances_from(&self, id: usize) -> Ratio<u128> {
+        let (x, y) = self.positions[id];
+        self.xs.distance_sum(x) + self.ys.distance_sum(y)
     }
 }
 impl Display for GalaxyMap {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for y in 0..self.height {
-            for x in 0..self.width {
-                write!(f, "{}", self.get(x, y))?;
+        let rows = self.occupied(self.height, |c| c.y);
+        let cols = self.occupied(self.width, |c| c.x);
+        // fractional factors are rounded down when drawn, so contracted empty lines vanish
+        let repeat = |filled: bool, factor: &Ratio<u128>| {
+            if filled {
+                1
+            } else {
+                factor.to_integer() as usize
+            }
+        };
+        for (y, &row) in rows.iter().enumerate() {
+            for _ in 0..repeat(row, &self.row_factor) {
+                for (x, &col) in cols.iter().enumerate() {
+                    for _ in 0..repeat(col, &self.col_factor) {
+                        write!(f, "{}", self.get(x, y))?;
+                    }
+                }
+                writeln!(f)?;
             }
-            writeln!(f)?;
         }
         Ok(())
     }
@@ -123,17 +282,35 @@ fn parse(input: &str) -> GalaxyMap {
             .collect(),
     )
 }
-fn part2(mut galaxy_map: GalaxyMap, expand_value: i32) -> i64 {
-    galaxy_map.expand(expand_value);
-    (0..galaxy_map.galaxies.len())
-        .combinations(2)
-        .map(|pair| galaxy_map.length(pair[0], pair[1]) as i64)
-        .sum()
+fn part2(mut galaxy_map: GalaxyMap, expand_value: u128) -> u128 {
+    galaxy_map.expand(expand_value.into(), expand_value.into());
+    galaxy_map.distance_sum().to_integer()
 }
 
 fn main() {
     let input = read_to_string("inputs/day11-input1.txt").unwrap();
     let galaxy_map = parse(&input);
+    if std::env::args().any(|arg| arg == "--queries") {
+        let mut expanded = galaxy_map.clone();
+        expanded.expand(1_000_000.into(), 1_000_000.into());
+        let index = expanded.index();
+        if let Some((a, b, _)) = index.farthest_pair() {
+            println!(
+                "farthest pair: {a} and {b}, {} apart",
+                expanded.length(a, b)
+            );
+        }
+        for (id, nearest) in index.nearest_all().into_iter().enumerate() {
+            if let Some((other, distance)) = nearest {
+                let total = index.distances_from(id);
+                println!("galaxy {id}: nearest is {other} at {distance}, {total} to all");
+            }
+        }
+        let histogram = index.histogram();
+        if let Some((distance, count)) = histogram.iter().max_by_key(|&(_, count)| count) {
+            println!("most common distance: {distance} ({count} pairs)");
+        }
+    }
     let answer = part2(galaxy_map, 1_000_000);
     println!("answer is: {answer}");
 }
@@ -166,4 +343,39 @@ mod tests {
         assert_eq!(part2(galaxy_map.clone(), 10), 1030);
         assert_eq!(part2(galaxy_map, 100), 8410);
     }
+    #[test]
+    fn per_axis_test() {
+        let mut galaxy_map = parse(INPUT.trim());
+        for (rows, cols) in [
+            (Ratio::from(2), Ratio::from(1)),
+            (Ratio::from(1), Ratio::from(1_000_000)),
+            (Ratio::new(1, 2), Ratio::new(3, 4)),
+            (Ratio::from(0), Ratio::from(0)),
+        ] {
+            galaxy_map.expand(rows, cols);
+            let brute = (0..galaxy_map.galaxies.len())
+                .flat_map(|a| (a + 1..galaxy_map.galaxies.len()).map(move |b| (a, b)))
+                .map(|(a, b)| galaxy_map.length(a, b))
+                .sum::<Ratio<u128>>();
+            assert_eq!(galaxy_map.distance_sum(), brute);
+        }
+        galaxy_map.expand(Ratio::new(1, 2), Ratio::new(1, 2));
+        assert_eq!(galaxy_map.distance_sum(), Ratio::from(251));
+        assert_eq!(galaxy_map.to_string().lines().count(), 8);
+        galaxy_map.expand(Ratio::new(3, 2), Ratio::one());
+        assert_eq!(galaxy_map.to_string().lines().count(), 10);
+    }
+    #[test]
+    fn index_test() {
+        let mut galaxy_map = parse(INPUT.trim());
+        galaxy_map.expand(1_000_000.into(), 1_000_000.into());
+        let index = galaxy_map.index();
+        let from_each = (0..galaxy_map.galaxies.len())
+            .map(|id| index.distances_from(id))
+            .sum::<Ratio<u128>>();
+        assert_eq!(from_each, galaxy_map.distance_sum() * 2);
+        let (_, _, farthest) = index.farthest_pair().unwrap();
+        assert_eq!(Some(&farthest), index.histogram().keys().last());
+        assert!(index.nearest_all().iter().all(Option::is_some));
+    }
 }
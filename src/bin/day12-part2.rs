@@ -1,8 +1,10 @@
 use indicatif::ProgressIterator;
-use std::cell::RefCell;
-use std::collections::HashMap;
-use std::fmt::Display;
+use std::collections::BTreeMap;
+use std::fmt::{Debug, Display};
 use std::fs::read_to_string;
+use std::ops::{AddAssign, SubAssign};
+
+use num::{BigUint, One, Zero};
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 enum Tile {
@@ -19,66 +21,168 @@ struct Spring {
 impl Spring {
     fn new((field, groups): (&str, &str)) -> Self {
         let field = field.chars().map(Tile::from).collect();
-        let groups = groups.split(',').map(|num| num.parse().unwrap()).collect();
-        Self { field, groups }
-    }
-    fn recursive(
-        &self,
-        field_i: usize,
-        group_i: usize,
-        cur_len: usize,
-        cache: &RefCell<HashMap<(usize, usize, usize), usize>>,
-    ) -> usize {
-        if cache.borrow().contains_key(&(field_i, group_i, cur_len)) {
-            return cache.borrow()[&(field_i, group_i, cur_len)];
-        }
-        if field_i == self.field.len() {
-            if (group_i == self.groups.len() && cur_len == 0)
-                || (group_i == self.groups.len() - 1 && self.groups[group_i] == cur_len)
-            {
-                return 1;
-            } else {
-                return 0;
+        Self {
+            field,
+            groups: parse_groups(groups),
+        }
+    }
+    fn arrangements(&self) -> u128 {
+        self.table().count()
+    }
+    fn big_arrangements(&self) -> BigUint {
+        self.table_with::<BigUint>().count()
+    }
+    fn table(&self) -> Table<'_, u128> {
+        Table::new(self)
+    }
+    fn table_with<T: Count>(&self) -> Table<'_, T> {
+        Table::new(self)
+    }
+    fn unfold(&mut self, count: usize) {
+        let field = std::mem::take(&mut self.field);
+        for copy in 0..count {
+            if copy > 0 {
+                self.field.push(Tile::Unknown);
             }
+            self.field.extend_from_slice(&field);
         }
-        let mut acc = 0;
-
-        let mut process_tile = |tile: Tile| {
-            if tile == Tile::Operational && cur_len == 0 {
-                acc += self.recursive(field_i + 1, group_i, cur_len, cache);
-            } else if tile == Tile::Operational
-                && cur_len > 0
-                && group_i < self.groups.len()
-                && cur_len == self.groups[group_i]
-            {
-                acc += self.recursive(field_i + 1, group_i + 1, 0, cache);
-            } else if tile == Tile::Damaged {
-                acc += self.recursive(field_i + 1, group_i, cur_len + 1, cache);
+        self.groups = self.groups.repeat(count);
+    }
+}
+
+#[derive(Debug)]
+struct Runs {
+    fits: BTreeMap<usize, Vec<bool>>,
+}
+impl Runs {
+    fn new(spring: &Spring) -> Self {
+        let n = spring.field.len();
+        let operational = std::iter::once(0)
+            .chain(spring.field.iter().scan(0, |acc, &tile| {
+                *acc += usize::from(tile == Tile::Operational);
+                Some(*acc)
+            }))
+            .collect::<Vec<_>>();
+        let fits = spring
+            .groups
+            .iter()
+            .map(|&len| {
+                let fits = (0..n)
+                    .map(|start| {
+                        let end = start + len;
+                        end <= n
+                            && operational[end] == operational[start]
+                            && spring.field.get(end) != Some(&Tile::Damaged)
+                    })
+                    .collect();
+                (len, fits)
+            })
+            .collect();
+        Self { fits }
+    }
+    fn fits(&self, start: usize, len: usize) -> bool {
+        self.fits[&len][start]
+    }
+}
+
+trait Count: Clone + Debug + Zero + One + PartialOrd + AddAssign + SubAssign {}
+impl<T: Clone + Debug + Zero + One + PartialOrd + AddAssign + SubAssign> Count for T {}
+
+#[derive(Debug)]
+struct Table<'a, T> {
+    spring: &'a Spring,
+    runs: Runs,
+    ways: Vec<Vec<T>>,
+}
+impl<'a, T: Count> Table<'a, T> {
+    fn new(spring: &'a Spring) -> Self {
+        let (n, groups) = (spring.field.len(), spring.groups.len());
+        let runs = Runs::new(spring);
+        let mut ways = vec![vec![T::zero(); groups + 1]; n + 1];
+        ways[n][groups] = T::one();
+        for i in (0..n).rev() {
+            for g in 0..=groups {
+                let mut total = T::zero();
+                if spring.field[i] != Tile::Damaged {
+                    total += ways[i + 1][g].clone();
+                }
+                if g < groups && runs.fits(i, spring.groups[g]) {
+                    let next = (i + spring.groups[g] + 1).min(n);
+                    total += ways[next][g + 1].clone();
+                }
+                ways[i][g] = total;
             }
+        }
+        Self { spring, runs, ways }
+    }
+    fn count(&self) -> T {
+        self.ways[0][0].clone()
+    }
+    fn choices(&self, i: usize, g: usize) -> [(Tile, T); 2] {
+        let spring = self.spring;
+        let n = spring.field.len();
+        let damaged = if g < spring.groups.len() && self.runs.fits(i, spring.groups[g]) {
+            self.ways[(i + spring.groups[g] + 1).min(n)][g + 1].clone()
+        } else {
+            T::zero()
         };
-
-        if self.field[field_i] == Tile::Unknown {
-            process_tile(Tile::Damaged);
-            process_tile(Tile::Operational);
+        let operational = if spring.field[i] != Tile::Damaged {
+            self.ways[i + 1][g].clone()
         } else {
-            process_tile(self.field[field_i]);
-        }
-
-        cache.borrow_mut().insert((field_i, group_i, cur_len), acc);
-        acc
+            T::zero()
+        };
+        [(Tile::Damaged, damaged), (Tile::Operational, operational)]
     }
-    fn arrangements(&self) -> usize {
-        let cache = RefCell::new(HashMap::new());
-        self.recursive(0, 0, 0, &cache)
+    fn walk(&self, mut pick: impl FnMut(usize, [(Tile, T); 2]) -> Tile) -> Option<Vec<Tile>> {
+        if self.count().is_zero() {
+            return None;
+        }
+        let n = self.spring.field.len();
+        let (mut i, mut g) = (0, 0);
+        let mut tiles = Vec::with_capacity(n);
+        while i < n {
+            if pick(i, self.choices(i, g)) == Tile::Damaged {
+                let len = self.spring.groups[g];
+                tiles.extend(std::iter::repeat_n(Tile::Damaged, len));
+                if i + len < n {
+                    tiles.push(Tile::Operational);
+                }
+                i = (i + len + 1).min(n);
+                g += 1;
+            } else {
+                tiles.push(Tile::Operational);
+                i += 1;
+            }
+        }
+        Some(tiles)
     }
-    fn unfold(&mut self, count: usize) {
-        let field_len = self.field.len();
-        let group_len = self.groups.len();
-        for _ in 0..count - 1 {
-            self.field.push(Tile::Unknown);
-            self.field.extend_from_within(0..field_len);
-            self.groups.extend_from_within(0..group_len);
+    fn nth(&self, mut k: T) -> Option<Vec<Tile>> {
+        if k >= self.count() {
+            return None;
         }
+        self.walk(|_, [(_, damaged), _]| {
+            if k < damaged {
+                Tile::Damaged
+            } else {
+                k -= damaged;
+                Tile::Operational
+            }
+        })
+    }
+    fn sample(&self, mut below: impl FnMut(T) -> T) -> Option<Vec<Tile>> {
+        self.walk(|_, [(_, damaged), (_, operational)]| {
+            let mut total = damaged.clone();
+            total += operational;
+            if below(total) < damaged {
+                Tile::Damaged
+            } else {
+                Tile::Operational
+            }
+        })
+    }
+    fn iter(&self) -> impl Iterator<Item = Vec<Tile>> + '_ {
+        std::iter::successors(Some(T::zero()), |k| Some(k.clone() + T::one()))
+            .map_while(|k| self.nth(k))
     }
 }
 impl From<char> for Tile {
@@ -117,6 +221,149 @@ impl Display for Spring {
     }
 }
 
+fn solve_line(line: &[Tile], clue: &[usize]) -> Option<Vec<Tile>> {
+    let mut solved = line.to_vec();
+    let mut spring = Spring {
+        field: line.to_vec(),
+        groups: clue.to_vec(),
+    };
+    if spring.table().count().is_zero() {
+        return None;
+    }
+    for i in 0..line.len() {
+        if line[i] != Tile::Unknown {
+            continue;
+        }
+        spring.field[i] = Tile::Damaged;
+        let damaged = !spring.table().count().is_zero();
+        spring.field[i] = Tile::Operational;
+        let operational = !spring.table().count().is_zero();
+        spring.field[i] = Tile::Unknown;
+        solved[i] = match (damaged, operational) {
+            (true, false) => Tile::Damaged,
+            (false, true) => Tile::Operational,
+            _ => Tile::Unknown,
+        };
+    }
+    Some(solved)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Grid {
+    cells: Vec<Vec<Tile>>,
+}
+impl Grid {
+    fn column(&self, col: usize) -> Vec<Tile> {
+        self.cells.iter().map(|row| row[col]).collect()
+    }
+    fn unknown(&self) -> Option<(usize, usize)> {
+        self.cells.iter().enumerate().find_map(|(r, row)| {
+            row.iter()
+                .position(|&tile| tile == Tile::Unknown)
+                .map(|c| (r, c))
+        })
+    }
+}
+impl Display for Grid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for row in &self.cells {
+            for tile in row {
+                write!(f, "{}", tile)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Solutions {
+    Zero,
+    One(Grid),
+    Many(Grid, Grid),
+}
+
+#[derive(Debug)]
+struct Nonogram {
+    rows: Vec<Vec<usize>>,
+    cols: Vec<Vec<usize>>,
+}
+impl Nonogram {
+    fn new(rows: Vec<Vec<usize>>, cols: Vec<Vec<usize>>) -> Self {
+        Self { rows, cols }
+    }
+    fn deduce(&self, grid: &mut Grid) -> bool {
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (r, clue) in self.rows.iter().enumerate() {
+                let Some(line) = solve_line(&grid.cells[r], clue) else {
+                    return false;
+                };
+                changed |= line != grid.cells[r];
+                grid.cells[r] = line;
+            }
+            for (c, clue) in self.cols.iter().enumerate() {
+                let column = grid.column(c);
+                let Some(line) = solve_line(&column, clue) else {
+                    return false;
+                };
+                changed |= line != column;
+                for (row, tile) in grid.cells.iter_mut().zip(line) {
+                    row[c] = tile;
+                }
+            }
+        }
+        true
+    }
+    fn solve(&self) -> Solutions {
+        let mut found = vec![];
+        let mut stack = vec![Grid {
+            cells: vec![vec![Tile::Unknown; self.cols.len()]; self.rows.len()],
+        }];
+        while let Some(mut grid) = stack.pop() {
+            if !self.deduce(&mut grid) {
+                continue;
+            }
+            match grid.unknown() {
+                None => {
+                    found.push(grid);
+                    if found.len() == 2 {
+                        break;
+                    }
+                }
+                Some((r, c)) => {
+                    for tile in [Tile::Operational, Tile::Damaged] {
+                        let mut guess = grid.clone();
+                        guess.cells[r][c] = tile;
+                        stack.push(guess);
+                    }
+                }
+            }
+        }
+        let mut found = found.into_iter();
+        match (found.next(), found.next()) {
+            (None, _) => Solutions::Zero,
+            (Some(grid), None) => Solutions::One(grid),
+            (Some(first), Some(second)) => Solutions::Many(first, second),
+        }
+    }
+}
+
+fn parse_groups(groups: &str) -> Vec<usize> {
+    groups
+        .split(',')
+        .filter(|num| !num.is_empty())
+        .map(|num| num.parse().unwrap())
+        .collect()
+}
+fn parse_nonogram(input: &str) -> Nonogram {
+    let (rows, cols) = input.split_once("\n\n").unwrap_or((input, ""));
+    Nonogram::new(
+        rows.lines().map(parse_groups).collect(),
+        cols.lines().map(parse_groups).collect(),
+    )
+}
 fn parse(input: &str) -> Vec<Spring> {
     input
         .lines()
@@ -124,20 +371,60 @@ fn parse(input: &str) -> Vec<Spring> {
         .map(Spring::new)
         .collect()
 }
-fn part2(mut springs: Vec<Spring>) -> usize {
+fn part2(mut springs: Vec<Spring>) -> u128 {
     springs.iter_mut().for_each(|spring| spring.unfold(5));
     springs.iter().progress().map(Spring::arrangements).sum()
 }
 
 fn main() {
+    if std::env::args().any(|arg| arg == "--nonogram") {
+        let input = read_to_string("inputs/day12-nonogram.txt").unwrap();
+        match parse_nonogram(input.trim()).solve() {
+            Solutions::Zero => println!("no solution"),
+            Solutions::One(grid) => print!("{grid}"),
+            Solutions::Many(first, second) => {
+                print!("several solutions, two of them:\n{first}\n{second}")
+            }
+        }
+        return;
+    }
     let input = read_to_string("inputs/day12-input1.txt").unwrap();
     let springs = parse(&input);
+    if std::env::args().any(|arg| arg == "--arrangements") {
+        let text = |tiles: Vec<Tile>| tiles.iter().map(Tile::to_string).collect::<String>();
+        let mut state = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos() as u64
+            | 1;
+        let mut below = |bound: u128| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            u128::from(state) % bound
+        };
+        for spring in &springs {
+            let table = spring.table();
+            println!("{spring} -> {} arrangements", table.count());
+            for tiles in table.iter().take(3) {
+                println!("  {}", text(tiles));
+            }
+            if let Some(tiles) = table.sample(&mut below) {
+                println!("  random: {}", text(tiles));
+            }
+            let mut unfolded = spring.clone();
+            unfolded.unfold(5);
+            println!("  unfolded: {}", unfolded.big_arrangements());
+        }
+    }
     let answer = part2(springs);
     println!("answer is: {answer}");
 }
 #[cfg(test)]
 mod tests {
 
+    use itertools::Itertools;
+
     use super::*;
 
     const INPUT: &str = r#"
@@ -161,4 +448,104 @@ mod tests {
         let springs = parse(INPUT.trim());
         assert_eq!(part2(springs), 525152);
     }
+    #[test]
+    fn concrete_arrangements() {
+        let springs = parse(INPUT.trim());
+        let table = springs[1].table();
+        assert_eq!(table.count(), springs[1].arrangements());
+        let rows = table
+            .iter()
+            .map(|tiles| tiles.iter().map(Tile::to_string).collect::<String>())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            vec![
+                ".#...#....###.",
+                ".#....#...###.",
+                "..#..#....###.",
+                "..#...#...###.",
+            ]
+        );
+        assert_eq!(
+            table.nth(2),
+            Some(rows[2].chars().map(Tile::from).collect())
+        );
+        assert_eq!(table.nth(4), None);
+        let mut state = 7;
+        let mut below = |bound: u128| {
+            state = (state * 1103515245 + 12345) % (1 << 31);
+            state % bound
+        };
+        for _ in 0..20 {
+            let sample = table.sample(&mut below).unwrap();
+            let row = sample.iter().map(Tile::to_string).collect::<String>();
+            assert!(rows.contains(&row));
+        }
+        let mut unfolded = springs[5].clone();
+        unfolded.unfold(5);
+        let table = unfolded.table();
+        assert_eq!(table.count(), 506250);
+        let text = |tiles: Vec<Tile>| tiles.iter().map(Tile::to_string).collect::<String>();
+        let last = text(table.nth(table.count() - 1).unwrap());
+        assert!(table
+            .iter()
+            .take(3)
+            .tuple_windows()
+            .all(|(a, b)| text(a) < text(b)));
+        assert!(table.iter().take(3).all(|tiles| text(tiles) < last));
+        assert_eq!(Spring::new(("?.", "2")).table().sample(|_| 0), None);
+    }
+    #[test]
+    fn nonogram() {
+        let plus = Nonogram::new(
+            vec![vec![1], vec![3], vec![1]],
+            vec![vec![1], vec![3], vec![1]],
+        );
+        let Solutions::One(grid) = plus.solve() else {
+            panic!("expected a unique solution");
+        };
+        assert_eq!(grid.to_string(), ".#.\n###\n.#.\n");
+        let heart = Nonogram::new(
+            vec![vec![1, 1], vec![5], vec![5], vec![3], vec![1]],
+            vec![vec![2], vec![4], vec![4], vec![4], vec![2]],
+        );
+        assert!(matches!(heart.solve(), Solutions::One(_)));
+        let diagonal = Nonogram::new(vec![vec![1], vec![1]], vec![vec![1], vec![1]]);
+        assert!(matches!(diagonal.solve(), Solutions::Many(_, _)));
+        let broken = Nonogram::new(vec![vec![2], vec![]], vec![vec![1], vec![]]);
+        assert_eq!(broken.solve(), Solutions::Zero);
+        let parsed = parse_nonogram("1\n3\n1\n\n1\n3\n1");
+        assert_eq!(parsed.rows, plus.rows);
+        assert_eq!(parsed.cols, plus.cols);
+        let empty = parse_nonogram("\n\n");
+        assert!(matches!(empty.solve(), Solutions::One(_)));
+    }
+    #[test]
+    fn edge_rows() {
+        assert_eq!(Spring::new(("", "")).arrangements(), 1);
+        assert_eq!(Spring::new(("???", "")).arrangements(), 1);
+        assert_eq!(Spring::new(("#??", "")).arrangements(), 0);
+        assert_eq!(Spring::new(("", "1")).arrangements(), 0);
+        assert_eq!(Spring::new(("#??", "1")).arrangements(), 1);
+        assert_eq!(Spring::new(("??#???", "1,1")).arrangements(), 3);
+        let mut spring = Spring::new(("???.###", "1,1,3"));
+        spring.unfold(0);
+        assert_eq!(spring.arrangements(), 1);
+        let mut spring = Spring::new(("?", "1"));
+        spring.unfold(40);
+        assert_eq!(spring.arrangements(), 1);
+        let mut spring = Spring::new(("??", "1"));
+        spring.unfold(40);
+        let expected = spring.arrangements();
+        assert_eq!(spring.big_arrangements(), BigUint::from(expected));
+        let mut spring = Spring::new(("???", ""));
+        spring.unfold(100);
+        assert_eq!(spring.big_arrangements(), BigUint::one());
+        let mut spring = Spring::new((".??..??...?##.", "1,1,3"));
+        spring.unfold(40);
+        assert_eq!(
+            spring.big_arrangements(),
+            BigUint::from(8_u8).pow(39) * 4_u8
+        );
+    }
 }
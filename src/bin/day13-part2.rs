@@ -1,71 +1,270 @@
+use std::fmt::Display;
 use std::fs::read_to_string;
 
+use anyhow::anyhow;
 use itertools::Itertools;
 
+const SMUDGES: usize = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Axis {
+    Horizontal,
+    Vertical,
+    Diagonal,
+    AntiDiagonal,
+    Rotation,
+}
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Reflection {
+    axis: Axis,
+    position: usize,
+    mismatches: usize,
+    smudges: Vec<(usize, usize)>,
+}
+impl Reflection {
+    fn score(&self) -> Option<usize> {
+        match self.axis {
+            Axis::Horizontal => Some(self.position * 100),
+            Axis::Vertical => Some(self.position),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Bits {
+    Packed(u128),
+    Vector(Vec<u64>),
+}
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Line {
+    bits: Bits,
+    len: usize,
+}
+impl Line {
+    fn new(cells: impl Iterator<Item = bool>) -> Self {
+        let cells = cells.collect_vec();
+        let len = cells.len();
+        let bits = if len <= 128 {
+            Bits::Packed(
+                cells
+                    .iter()
+                    .enumerate()
+                    .fold(0, |acc, (i, &set)| acc | (u128::from(set) << i)),
+            )
+        } else {
+            let mut words = vec![0; len.div_ceil(64)];
+            for (i, &set) in cells.iter().enumerate() {
+                words[i / 64] |= u64::from(set) << (i % 64);
+            }
+            Bits::Vector(words)
+        };
+        Self { bits, len }
+    }
+    fn get(&self, i: usize) -> bool {
+        match &self.bits {
+            Bits::Packed(bits) => bits >> i & 1 == 1,
+            Bits::Vector(words) => words[i / 64] >> (i % 64) & 1 == 1,
+        }
+    }
+    fn diff(&self, other: &Line) -> Option<usize> {
+        if self.len != other.len {
+            return None;
+        }
+        Some(match (&self.bits, &other.bits) {
+            (Bits::Packed(a), Bits::Packed(b)) => (a ^ b).count_ones() as usize,
+            (Bits::Vector(a), Bits::Vector(b)) => a
+                .iter()
+                .zip(b)
+                .map(|(a, b)| (a ^ b).count_ones() as usize)
+                .sum(),
+            _ => self.diff_positions(other).len(),
+        })
+    }
+    fn diff_positions(&self, other: &Line) -> Vec<usize> {
+        (0..self.len)
+            .filter(|&i| self.get(i) != other.get(i))
+            .collect()
+    }
+}
+impl Display for Line {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for i in 0..self.len {
+            write!(f, "{}", if self.get(i) { '#' } else { '.' })?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 struct Pattern {
-    rows: Vec<String>,
-    cols: Vec<String>,
+    rows: Vec<Line>,
+    cols: Vec<Line>,
 }
 impl Pattern {
-    fn new(lines: Vec<&str>) -> Self {
-        let rows: Vec<_> = lines.into_iter().map(String::from).collect();
-        let mut cols = Vec::with_capacity(rows[0].len());
-        for c in 0..rows[0].len() {
-            let mut col = String::with_capacity(rows.len());
-            for row in rows.iter() {
-                col.push(row.as_bytes()[c] as char)
+    fn new(lines: Vec<&str>) -> anyhow::Result<Self> {
+        let Some(width) = lines.first().map(|line| line.len()) else {
+            return Err(anyhow!("empty pattern"));
+        };
+        if let Some(row) = lines.iter().position(|line| line.len() != width) {
+            return Err(anyhow!(
+                "row {row} is {} wide, expected {width}",
+                lines[row].len()
+            ));
+        }
+        let grid = lines
+            .iter()
+            .map(|line| line.bytes().map(|b| b == b'#').collect_vec())
+            .collect_vec();
+        let rows = grid
+            .iter()
+            .map(|row| Line::new(row.iter().copied()))
+            .collect();
+        let cols = (0..width)
+            .map(|c| Line::new(grid.iter().map(|row| row[c])))
+            .collect();
+        Ok(Self { rows, cols })
+    }
+    fn get(&self, row: usize, col: usize) -> bool {
+        self.rows[row].get(col)
+    }
+    fn symmetric(
+        &self,
+        axis: Axis,
+        pairs: impl Iterator<Item = ((usize, usize), (usize, usize))>,
+        max_smudges: usize,
+    ) -> Option<Reflection> {
+        let mut smudges = vec![];
+        for (a, b) in pairs {
+            if self.get(a.0, a.1) != self.get(b.0, b.1) {
+                smudges.push(a);
+                if smudges.len() > max_smudges {
+                    return None;
+                }
             }
-            cols.push(col)
         }
-
-        Self { rows, cols }
+        Some(Reflection {
+            axis,
+            position: 0,
+            mismatches: smudges.len(),
+            smudges,
+        })
+    }
+    fn reflections(&self, max_smudges: usize) -> Vec<Reflection> {
+        let (height, width) = (self.rows.len(), self.cols.len());
+        let mut reflections = vec![];
+        for (position, smudges) in line_reflections(&self.rows, max_smudges) {
+            reflections.push(Reflection {
+                axis: Axis::Horizontal,
+                position,
+                mismatches: smudges.len(),
+                smudges,
+            });
+        }
+        for (position, smudges) in line_reflections(&self.cols, max_smudges) {
+            reflections.push(Reflection {
+                axis: Axis::Vertical,
+                position,
+                mismatches: smudges.len(),
+                smudges: smudges.into_iter().map(|(c, r)| (r, c)).collect(),
+            });
+        }
+        if height == width {
+            let n = height;
+            let upper = move || (0..n).flat_map(move |r| (r + 1..n).map(move |c| (r, c)));
+            reflections.extend(self.symmetric(
+                Axis::Diagonal,
+                upper().map(|(r, c)| ((r, c), (c, r))),
+                max_smudges,
+            ));
+            reflections.extend(
+                self.symmetric(
+                    Axis::AntiDiagonal,
+                    (0..n)
+                        .flat_map(move |r| (0..n - 1 - r).map(move |c| (r, c)))
+                        .map(|(r, c)| ((r, c), (n - 1 - c, n - 1 - r))),
+                    max_smudges,
+                ),
+            );
+        }
+        reflections.extend(
+            self.symmetric(
+                Axis::Rotation,
+                (0..height)
+                    .flat_map(|r| (0..width).map(move |c| (r, c)))
+                    .take(height * width / 2)
+                    .map(|(r, c)| ((r, c), (height - 1 - r, width - 1 - c))),
+                max_smudges,
+            ),
+        );
+        reflections
+    }
+    fn summary(&self, smudges: usize) -> Option<usize> {
+        self.reflections(smudges)
+            .iter()
+            .filter(|reflection| reflection.mismatches == smudges)
+            .find_map(Reflection::score)
     }
 }
 
-fn parse(input: &str) -> Vec<Pattern> {
+fn parse(input: &str) -> anyhow::Result<Vec<Pattern>> {
     input
+        .trim()
         .split("\n\n")
-        .map(|pat| pat.lines().collect())
-        .map(Pattern::new)
+        .map(|pat| Pattern::new(pat.lines().collect()))
         .collect()
 }
-fn mirror_pos(lines: &[String]) -> Option<usize> {
-    lines
-        .iter()
-        .enumerate()
-        .tuple_windows()
-        .filter(|((_, a), (_, b))| {
-            a == b || a.chars().zip(b.chars()).filter(|(a, b)| a != b).count() <= 1
-        })
-        .find_map(|((a_i, _), (b_i, _))| {
-            let top = &lines[0..=a_i];
-            let btm = &lines[b_i..];
-            (top.iter()
-                .rev()
-                .flat_map(|line| line.chars())
-                .zip(btm.iter().flat_map(|line| line.chars()))
-                .filter(|(a, b)| a != b)
-                .count()
-                == 1)
-                .then_some(a_i + 1)
+fn line_reflections(lines: &[Line], max_smudges: usize) -> Vec<(usize, Vec<(usize, usize)>)> {
+    (1..lines.len())
+        .filter_map(|position| {
+            let pairs = (0..position).rev().zip(position..lines.len());
+            let mut mismatches = 0;
+            for (line_i, other_i) in pairs.clone() {
+                mismatches += lines[line_i].diff(&lines[other_i])?;
+                if mismatches > max_smudges {
+                    return None;
+                }
+            }
+            let smudges = pairs
+                .filter(|_| mismatches > 0)
+                .flat_map(|(line_i, other_i)| {
+                    let positions = lines[line_i].diff_positions(&lines[other_i]);
+                    positions.into_iter().map(move |i| (line_i, i))
+                })
+                .collect();
+            Some((position, smudges))
         })
+        .collect()
+}
+fn mirror_pos(lines: &[Line]) -> Option<usize> {
+    line_reflections(lines, SMUDGES)
+        .into_iter()
+        .find(|(_, smudges)| smudges.len() == SMUDGES)
+        .map(|(position, _)| position)
 }
+
 fn part2(patterns: &[Pattern]) -> usize {
     patterns
         .iter()
-        .map(|pattern| {
-            mirror_pos(&pattern.rows)
-                .map(|left| left * 100)
-                .or_else(|| mirror_pos(&pattern.cols))
-                .unwrap_or(0)
-        })
+        .filter_map(|pattern| pattern.summary(SMUDGES))
         .sum()
 }
 
 fn main() {
     let input = read_to_string("inputs/day13-input1.txt").unwrap();
-    let patterns = parse(&input);
+    let patterns = parse(&input).unwrap();
+    if std::env::args().any(|arg| arg == "--reflections") {
+        for (i, pattern) in patterns.iter().enumerate() {
+            let (rows, cols) = (mirror_pos(&pattern.rows), mirror_pos(&pattern.cols));
+            println!("pattern {i}: mirrored rows {rows:?}, columns {cols:?}");
+            for reflection in pattern.reflections(SMUDGES) {
+                println!(
+                    "  {:?} at {} with {} mismatches",
+                    reflection.axis, reflection.position, reflection.mismatches
+                );
+            }
+        }
+    }
     let answer = part2(&patterns);
     println!("answer is: {answer}");
 }
@@ -95,14 +294,14 @@ mod tests {
 
     #[test]
     fn parsing() {
-        let patterns = parse(INPUT.trim());
+        let patterns = parse(INPUT.trim()).unwrap();
         assert_eq!(patterns.len(), 2);
-        assert_eq!(patterns[0].rows[0], "#.##..##.");
-        assert_eq!(patterns[0].cols[0], "#.##..#");
+        assert_eq!(patterns[0].rows[0].to_string(), "#.##..##.");
+        assert_eq!(patterns[0].cols[0].to_string(), "#.##..#");
     }
     #[test]
     fn mirror_test() {
-        let patterns = parse(INPUT.trim());
+        let patterns = parse(INPUT.trim()).unwrap();
 
         assert_eq!(mirror_pos(&patterns[0].rows), Some(3));
         assert_eq!(mirror_pos(&patterns[1].rows), Some(1));
@@ -110,7 +309,66 @@ mod tests {
 
     #[test]
     fn part2_test() {
-        let patterns = parse(INPUT.trim());
-        assert_eq!(part2(&patterns), 402);
+        let patterns = parse(INPUT.trim()).unwrap();
+        assert_eq!(part2(&patterns), 400);
+    }
+    #[test]
+    fn reflections_test() {
+        let patterns = parse(INPUT.trim()).unwrap();
+        let found = patterns[1].reflections(1);
+        assert_eq!(
+            found,
+            vec![
+                Reflection {
+                    axis: Axis::Horizontal,
+                    position: 1,
+                    mismatches: 1,
+                    smudges: vec![(0, 4)],
+                },
+                Reflection {
+                    axis: Axis::Horizontal,
+                    position: 4,
+                    mismatches: 0,
+                    smudges: vec![],
+                },
+            ]
+        );
+        assert_eq!(patterns[0].reflections(0)[0].axis, Axis::Vertical);
+        assert_eq!(patterns[0].reflections(1)[0].smudges, vec![(0, 0)]);
+
+        let square = Pattern::new(vec!["#..", "...", "..."]).unwrap();
+        let axes = |max| {
+            square
+                .reflections(max)
+                .into_iter()
+                .map(|r| (r.axis, r.mismatches))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            axes(0),
+            vec![
+                (Axis::Horizontal, 0),
+                (Axis::Vertical, 0),
+                (Axis::Diagonal, 0)
+            ]
+        );
+        let rotation = square
+            .reflections(1)
+            .into_iter()
+            .find(|r| r.axis == Axis::Rotation)
+            .unwrap();
+        assert_eq!(rotation.smudges, vec![(0, 0)]);
+        assert_eq!(square.summary(1), Some(100));
+    }
+    #[test]
+    fn ragged_patterns() {
+        assert!(parse("").is_err());
+        assert_eq!(parse("#.\n.#\n\n").unwrap().len(), 1);
+        assert!(parse("#.#\n#.").is_err());
+        assert!(parse("#.\n.#\n\n#.#\n#.").is_err());
+        let short = Line::new([true, false].into_iter());
+        let long = Line::new((0..200).map(|i| i % 3 == 0));
+        assert_eq!(short.diff(&long), None);
+        assert_eq!(long.diff(&long.clone()), Some(0));
     }
 }
@@ -1,75 +1,388 @@
-use std::collections::HashSet;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fmt::Display;
 use std::fs::read_to_string;
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
 
-fn parse(input: &str) -> Vec<Vec<char>> {
-    input.lines().map(|line| line.chars().collect()).collect()
-}
-fn tilt(platform: Vec<Vec<char>>) -> Vec<Vec<char>> {
-    let mut tilted = platform.clone();
-    let w = platform[0].len();
-    let h = platform.len();
-    let mut row_d = platform[h - 1].clone();
-    let mut round_rocks_queue: HashSet<(usize, usize)> = HashSet::new();
+fn part1(platform: Platform) -> anyhow::Result<usize> {
+    let program = parse_program("N")?;
+    Ok(platform.run(&program).total_load())
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Dir {
+    North,
+    West,
+    South,
+    East,
+}
+const SPIN: [Dir; 4] = [Dir::North, Dir::West, Dir::South, Dir::East];
+impl TryFrom<&str> for Dir {
+    type Error = anyhow::Error;
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "N" => Ok(Dir::North),
+            "W" => Ok(Dir::West),
+            "S" => Ok(Dir::South),
+            "E" => Ok(Dir::East),
+            _ => Err(anyhow::anyhow!("unknown direction {value:?}")),
+        }
+    }
+}
 
-    for r in (1..h).rev() {
-        let mut row_u = platform[r - 1].clone();
-        for c in 0..w {
-            match (row_d[c], row_u[c]) {
-                ('O', '.') => {
-                    let to_remove = round_rocks_queue
-                        .iter()
-                        .filter(|(_, col)| c == *col)
-                        .copied()
-                        .max_by_key(|(r, _)| *r);
-                    if let Some(key) = to_remove {
-                        round_rocks_queue.remove(&key);
-                        tilted[key.0][key.1] = '.';
-                        row_d[c] = 'O';
-                        row_u[c] = 'O';
-                        round_rocks_queue.insert((r, c));
-                    } else {
-                        row_d[c] = '.';
-                        row_u[c] = 'O'
-                    }
-                }
-                ('O', 'O') => {
-                    round_rocks_queue.insert((r, c));
-                }
-                ('O', '#') => {
-                    round_rocks_queue.retain(|key| key.1 != c);
-                }
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Program {
+    blocks: Vec<(Vec<Dir>, usize)>,
+}
+fn parse_program(input: &str) -> anyhow::Result<Program> {
+    let mut blocks = vec![];
+    let mut dirs = vec![];
+    for token in input.split_whitespace() {
+        if let Some(times) = token.strip_prefix('x') {
+            if dirs.is_empty() {
+                return Err(anyhow::anyhow!("repeat {token:?} without directions"));
+            }
+            blocks.push((std::mem::take(&mut dirs), times.parse()?));
+        } else {
+            dirs.push(Dir::try_from(token)?);
+        }
+    }
+    if !dirs.is_empty() {
+        blocks.push((dirs, 1));
+    }
+    Ok(Program { blocks })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct BitSet {
+    words: Vec<u64>,
+}
+impl BitSet {
+    fn new(len: usize) -> Self {
+        Self {
+            words: vec![0; len.div_ceil(64)],
+        }
+    }
+    fn get(&self, i: usize) -> bool {
+        self.words[i / 64] >> (i % 64) & 1 == 1
+    }
+    fn set(&mut self, i: usize) {
+        self.words[i / 64] |= 1 << (i % 64);
+    }
+    fn unset(&mut self, i: usize) {
+        self.words[i / 64] &= !(1 << (i % 64));
+    }
+    fn masks(range: Range<usize>) -> impl Iterator<Item = (usize, u64)> {
+        let words = range.start / 64..range.end.div_ceil(64);
+        words.map(move |w| {
+            let lo = range.start.max(w * 64) - w * 64;
+            let hi = range.end.min(w * 64 + 64) - w * 64;
+            let ones = if hi - lo == 64 {
+                !0
+            } else {
+                (1 << (hi - lo)) - 1
+            };
+            (w, ones << lo)
+        })
+    }
+    fn count(&self, range: Range<usize>) -> usize {
+        Self::masks(range)
+            .map(|(w, mask)| (self.words[w] & mask).count_ones() as usize)
+            .sum()
+    }
+    fn fill(&mut self, range: Range<usize>, set: bool) {
+        for (w, mask) in Self::masks(range) {
+            if set {
+                self.words[w] |= mask;
+            } else {
+                self.words[w] &= !mask;
+            }
+        }
+    }
+    fn ones(&self) -> impl Iterator<Item = usize> + '_ {
+        self.ones_in(0..self.words.len() * 64)
+    }
+    fn ones_in(&self, range: Range<usize>) -> impl Iterator<Item = usize> + '_ {
+        Self::masks(range).flat_map(|(w, mask)| {
+            let mut word = self.words[w] & mask;
+            std::iter::from_fn(move || {
+                (word != 0).then(|| {
+                    let bit = word.trailing_zeros() as usize;
+                    word &= word - 1;
+                    w * 64 + bit
+                })
+            })
+        })
+    }
+}
+fn transpose(lines: &[BitSet], len: usize) -> Vec<BitSet> {
+    let mut transposed = vec![BitSet::new(lines.len()); len];
+    for (i, line) in lines.iter().enumerate() {
+        for j in line.ones() {
+            transposed[j].set(i);
+        }
+    }
+    transposed
+}
+fn segments(cubes: &BitSet, len: usize) -> Vec<Range<usize>> {
+    let mut segments = vec![];
+    let mut start = 0;
+    for cube in cubes.ones().chain(std::iter::once(len)) {
+        if start < cube {
+            segments.push(start..cube);
+        }
+        start = cube + 1;
+    }
+    segments
+}
+fn roll(
+    lines: &mut [BitSet],
+    cross: &mut [BitSet],
+    segments: &[Vec<Range<usize>>],
+    to_start: bool,
+) {
+    for (i, (line, segments)) in lines.iter_mut().zip(segments).enumerate() {
+        for segment in segments {
+            let count = line.count(segment.clone());
+            let rolled = if to_start {
+                segment.start..segment.start + count
+            } else {
+                segment.end - count..segment.end
+            };
+            if line.count(rolled.clone()) == count {
+                continue;
+            }
+            for j in line.ones_in(segment.clone()) {
+                cross[j].unset(i);
+            }
+            line.fill(segment.clone(), false);
+            line.fill(rolled.clone(), true);
+            for j in rolled {
+                cross[j].set(i);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Platform {
+    round_rocks: Vec<BitSet>,
+    round_cols: Vec<BitSet>,
+    cube_rocks: Vec<BitSet>,
+    row_segments: Vec<Vec<Range<usize>>>,
+    col_segments: Vec<Vec<Range<usize>>>,
+    width: usize,
+    height: usize,
+}
 
-                _ => (),
+impl Platform {
+    fn new(round_rocks: Vec<BitSet>, cube_rocks: Vec<BitSet>, width: usize) -> Self {
+        let height = round_rocks.len();
+        let row_segments = cube_rocks
+            .iter()
+            .map(|cubes| segments(cubes, width))
+            .collect();
+        let col_segments = transpose(&cube_rocks, width)
+            .iter()
+            .map(|cubes| segments(cubes, height))
+            .collect();
+        let round_cols = transpose(&round_rocks, width);
+        Self {
+            round_rocks,
+            round_cols,
+            cube_rocks,
+            row_segments,
+            col_segments,
+            width,
+            height,
+        }
+    }
+    fn get(&self, x: usize, y: usize) -> char {
+        if self.round_rocks[y].get(x) {
+            'O'
+        } else if self.cube_rocks[y].get(x) {
+            '#'
+        } else {
+            '.'
+        }
+    }
+    fn total_load(&self) -> usize {
+        self.load(Dir::North)
+    }
+    fn load(&self, edge: Dir) -> usize {
+        self.round_rocks
+            .iter()
+            .enumerate()
+            .flat_map(|(y, row)| row.ones().map(move |x| (x, y)))
+            .map(|(x, y)| match edge {
+                Dir::North => self.height - y,
+                Dir::South => y + 1,
+                Dir::West => self.width - x,
+                Dir::East => x + 1,
+            })
+            .sum()
+    }
+    fn loads(&self) -> [usize; 4] {
+        SPIN.map(|edge| self.load(edge))
+    }
+    fn tilt(mut self, dir: Dir) -> Self {
+        match dir {
+            Dir::West | Dir::East => roll(
+                &mut self.round_rocks,
+                &mut self.round_cols,
+                &self.row_segments,
+                dir == Dir::West,
+            ),
+            Dir::North | Dir::South => roll(
+                &mut self.round_cols,
+                &mut self.round_rocks,
+                &self.col_segments,
+                dir == Dir::North,
+            ),
+        }
+        self
+    }
+    fn tilt_all(self, dirs: &[Dir]) -> Self {
+        dirs.iter().fold(self, |platform, &dir| platform.tilt(dir))
+    }
+    fn state(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.round_rocks.hash(&mut hasher);
+        hasher.finish()
+    }
+    fn find_period(
+        self,
+        dirs: &[Dir],
+        times: usize,
+        mut visit: impl FnMut(&Self),
+    ) -> (Self, Option<(usize, usize)>) {
+        let mut seen = HashMap::<u64, Vec<usize>>::new();
+        let mut platform = self;
+        for done in 0..=times {
+            let candidates = seen.entry(platform.state()).or_default();
+            // a hash hit is only a candidate: spin one more period and check the
+            // platform comes back to the state it was hit with
+            let start = candidates.iter().copied().find(|&start| {
+                let spun = (start..done).fold(platform.clone(), |p, _| p.tilt_all(dirs));
+                spun.round_rocks == platform.round_rocks
+            });
+            if let Some(start) = start {
+                return (platform, Some((start, done)));
+            }
+            candidates.push(done);
+            visit(&platform);
+            if done < times {
+                platform = platform.tilt_all(dirs);
             }
         }
-        tilted[r] = row_d.clone();
-        tilted[r - 1] = row_u.clone();
-        row_d = row_u;
-    }
-    tilted
-}
-fn part1(platform: &[Vec<char>]) -> usize {
-    let h = platform.len();
-    platform
-        .iter()
-        .enumerate()
-        .map(|(i, row)| row.iter().filter(|ch| **ch == 'O').count() * (h - i))
-        .sum()
-}
-fn _print(platform: &[Vec<char>]) {
-    for row in platform.iter() {
-        for t in row.iter() {
-            print!("{t}");
+        (platform, None)
+    }
+    fn repeat(self, dirs: &[Dir], times: usize) -> Self {
+        let (mut platform, period) = self.find_period(dirs, times, |_| {});
+        if let Some((start, done)) = period {
+            for _ in 0..(times - done) % (done - start) {
+                platform = platform.tilt_all(dirs);
+            }
+        }
+        platform
+    }
+    fn series(self, dirs: &[Dir]) -> LoadSeries {
+        let mut loads = vec![];
+        let (_, period) =
+            self.find_period(dirs, usize::MAX, |platform| loads.push(platform.loads()));
+        let (start, _) = period.expect("a platform has finitely many states");
+        LoadSeries { loads, start }
+    }
+    fn run(self, program: &Program) -> Self {
+        program.blocks.iter().fold(self, |platform, (dirs, times)| {
+            platform.repeat(dirs, *times)
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct LoadSeries {
+    loads: Vec<[usize; 4]>,
+    start: usize,
+}
+impl LoadSeries {
+    fn index(&self, cycles: usize) -> usize {
+        if cycles < self.loads.len() {
+            cycles
+        } else {
+            self.start + (cycles - self.start) % self.period()
+        }
+    }
+    fn period(&self) -> usize {
+        self.loads.len() - self.start
+    }
+    fn at(&self, cycles: usize, edge: Dir) -> usize {
+        self.loads[self.index(cycles)][edge as usize]
+    }
+    fn prefix(&self, edge: Dir) -> Vec<usize> {
+        self.loads[..self.start]
+            .iter()
+            .map(|loads| loads[edge as usize])
+            .collect()
+    }
+    fn periodic(&self, edge: Dir) -> Vec<usize> {
+        self.loads[self.start..]
+            .iter()
+            .map(|loads| loads[edge as usize])
+            .collect()
+    }
+}
+
+impl Display for Platform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                write!(f, "{}", self.get(x, y))?;
+            }
+            writeln!(f)?;
         }
-        println!();
+        Ok(())
     }
-    println!();
+}
+
+fn parse(input: &str) -> Platform {
+    let width = input.lines().map(str::len).max().unwrap_or(0);
+    let (round_rocks, cube_rocks) = input
+        .lines()
+        .map(|line| {
+            let (mut round, mut cube) = (BitSet::new(width), BitSet::new(width));
+            for (x, char) in line.chars().enumerate() {
+                match char {
+                    '#' => cube.set(x),
+                    'O' => round.set(x),
+                    _ => {}
+                }
+            }
+            (round, cube)
+        })
+        .unzip();
+    Platform::new(round_rocks, cube_rocks, width)
 }
 fn main() {
     let input = read_to_string("inputs/day14-input1.txt").unwrap();
-    let rocks = parse(&input);
-    let tilted = tilt(rocks);
-    let answer = part1(&tilted);
+    let platform = parse(&input);
+    if std::env::args().any(|arg| arg == "--series") {
+        let series = platform.clone().series(&SPIN);
+        println!(
+            "north loads before the loop: {:?}",
+            series.prefix(Dir::North)
+        );
+        println!(
+            "north loads over a period of {}: {:?}",
+            series.period(),
+            series.periodic(Dir::North)
+        );
+        println!(
+            "north load after 1000000000 spins: {}",
+            series.at(1_000_000_000, Dir::North)
+        );
+    }
+    let answer = part1(platform).unwrap();
     println!("answer is: {answer}");
 }
 #[cfg(test)]
@@ -103,21 +416,33 @@ O..#.OO...
 "#;
     #[test]
     fn parsing() {
-        let rocks = parse(INPUT.trim());
-        assert_eq!(rocks[0][0], 'O');
-        assert_eq!(rocks[0][5], '#');
-        assert_eq!(rocks[2][0], '.');
+        let platform = parse(INPUT.trim());
+        assert_eq!(platform.get(0, 0), 'O');
+        assert_eq!(platform.get(5, 0), '#');
+        assert_eq!(platform.get(0, 2), '.');
+        assert_eq!((platform.width, platform.height), (10, 10));
     }
     #[test]
     fn tilt_test() {
-        let rocks = parse(INPUT.trim());
-        let tilted = tilt(rocks);
+        let platform = parse(INPUT.trim());
+        let tilted = platform.tilt(Dir::North);
         assert_eq!(tilted, parse(TILTED.trim()));
     }
     #[test]
     fn part1_test() {
-        let rocks = parse(INPUT.trim());
-        let tilted = tilt(rocks);
-        assert_eq!(part1(&tilted), 136);
+        let platform = parse(INPUT.trim());
+        assert_eq!(part1(platform).unwrap(), 136);
+    }
+    #[test]
+    fn north_series() -> anyhow::Result<()> {
+        let platform = parse(INPUT.trim());
+        let series = platform.clone().series(&[Dir::North]);
+        assert_eq!((series.start, series.period()), (1, 1));
+        assert_eq!(series.prefix(Dir::North), vec![104]);
+        assert_eq!(series.periodic(Dir::North), vec![136]);
+        assert_eq!(series.at(1_000, Dir::North), 136);
+        let cycled = platform.clone().tilt_all(&SPIN);
+        assert_eq!(cycled, platform.run(&parse_program("N W S E")?));
+        Ok(())
     }
 }
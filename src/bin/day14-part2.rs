@@ -1,114 +1,343 @@
-use std::collections::HashSet;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
 use std::fmt::Display;
 use std::fs::read_to_string;
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
 
-use glam::IVec2;
-
+fn part2(platform: Platform) -> anyhow::Result<usize> {
+    let program = parse_program("N W S E x1000000000")?;
+    Ok(platform.run(&program).total_load())
+}
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-struct Dir(IVec2);
-const NORTH: Dir = Dir(IVec2::Y);
-const SOUTH: Dir = Dir(IVec2::NEG_Y);
-const WEST: Dir = Dir(IVec2::X);
-const EAST: Dir = Dir(IVec2::NEG_X);
+enum Dir {
+    North,
+    West,
+    South,
+    East,
+}
+const SPIN: [Dir; 4] = [Dir::North, Dir::West, Dir::South, Dir::East];
+impl TryFrom<&str> for Dir {
+    type Error = anyhow::Error;
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "N" => Ok(Dir::North),
+            "W" => Ok(Dir::West),
+            "S" => Ok(Dir::South),
+            "E" => Ok(Dir::East),
+            _ => Err(anyhow::anyhow!("unknown direction {value:?}")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Program {
+    blocks: Vec<(Vec<Dir>, usize)>,
+}
+fn parse_program(input: &str) -> anyhow::Result<Program> {
+    let mut blocks = vec![];
+    let mut dirs = vec![];
+    for token in input.split_whitespace() {
+        if let Some(times) = token.strip_prefix('x') {
+            if dirs.is_empty() {
+                return Err(anyhow::anyhow!("repeat {token:?} without directions"));
+            }
+            blocks.push((std::mem::take(&mut dirs), times.parse()?));
+        } else {
+            dirs.push(Dir::try_from(token)?);
+        }
+    }
+    if !dirs.is_empty() {
+        blocks.push((dirs, 1));
+    }
+    Ok(Program { blocks })
+}
 
-fn part2(platform: Platform) -> i32 {
-    platform.spin_1_000_000_000().total_load()
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct BitSet {
+    words: Vec<u64>,
+}
+impl BitSet {
+    fn new(len: usize) -> Self {
+        Self {
+            words: vec![0; len.div_ceil(64)],
+        }
+    }
+    fn get(&self, i: usize) -> bool {
+        self.words[i / 64] >> (i % 64) & 1 == 1
+    }
+    fn set(&mut self, i: usize) {
+        self.words[i / 64] |= 1 << (i % 64);
+    }
+    fn unset(&mut self, i: usize) {
+        self.words[i / 64] &= !(1 << (i % 64));
+    }
+    fn masks(range: Range<usize>) -> impl Iterator<Item = (usize, u64)> {
+        let words = range.start / 64..range.end.div_ceil(64);
+        words.map(move |w| {
+            let lo = range.start.max(w * 64) - w * 64;
+            let hi = range.end.min(w * 64 + 64) - w * 64;
+            let ones = if hi - lo == 64 {
+                !0
+            } else {
+                (1 << (hi - lo)) - 1
+            };
+            (w, ones << lo)
+        })
+    }
+    fn count(&self, range: Range<usize>) -> usize {
+        Self::masks(range)
+            .map(|(w, mask)| (self.words[w] & mask).count_ones() as usize)
+            .sum()
+    }
+    fn fill(&mut self, range: Range<usize>, set: bool) {
+        for (w, mask) in Self::masks(range) {
+            if set {
+                self.words[w] |= mask;
+            } else {
+                self.words[w] &= !mask;
+            }
+        }
+    }
+    fn ones(&self) -> impl Iterator<Item = usize> + '_ {
+        self.ones_in(0..self.words.len() * 64)
+    }
+    fn ones_in(&self, range: Range<usize>) -> impl Iterator<Item = usize> + '_ {
+        Self::masks(range).flat_map(|(w, mask)| {
+            let mut word = self.words[w] & mask;
+            std::iter::from_fn(move || {
+                (word != 0).then(|| {
+                    let bit = word.trailing_zeros() as usize;
+                    word &= word - 1;
+                    w * 64 + bit
+                })
+            })
+        })
+    }
 }
-#[derive(Debug, Clone, Default, PartialEq, Eq)]
+fn transpose(lines: &[BitSet], len: usize) -> Vec<BitSet> {
+    let mut transposed = vec![BitSet::new(lines.len()); len];
+    for (i, line) in lines.iter().enumerate() {
+        for j in line.ones() {
+            transposed[j].set(i);
+        }
+    }
+    transposed
+}
+fn segments(cubes: &BitSet, len: usize) -> Vec<Range<usize>> {
+    let mut segments = vec![];
+    let mut start = 0;
+    for cube in cubes.ones().chain(std::iter::once(len)) {
+        if start < cube {
+            segments.push(start..cube);
+        }
+        start = cube + 1;
+    }
+    segments
+}
+fn roll(
+    lines: &mut [BitSet],
+    cross: &mut [BitSet],
+    segments: &[Vec<Range<usize>>],
+    to_start: bool,
+) {
+    for (i, (line, segments)) in lines.iter_mut().zip(segments).enumerate() {
+        for segment in segments {
+            let count = line.count(segment.clone());
+            let rolled = if to_start {
+                segment.start..segment.start + count
+            } else {
+                segment.end - count..segment.end
+            };
+            if line.count(rolled.clone()) == count {
+                continue;
+            }
+            for j in line.ones_in(segment.clone()) {
+                cross[j].unset(i);
+            }
+            line.fill(segment.clone(), false);
+            line.fill(rolled.clone(), true);
+            for j in rolled {
+                cross[j].set(i);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 struct Platform {
-    round_rocks: HashSet<IVec2>,
-    cube_rocks: HashSet<IVec2>,
-    size: i32,
+    round_rocks: Vec<BitSet>,
+    round_cols: Vec<BitSet>,
+    cube_rocks: Vec<BitSet>,
+    row_segments: Vec<Vec<Range<usize>>>,
+    col_segments: Vec<Vec<Range<usize>>>,
+    width: usize,
+    height: usize,
 }
 
 impl Platform {
-    fn total_load(&self) -> i32 {
+    fn new(round_rocks: Vec<BitSet>, cube_rocks: Vec<BitSet>, width: usize) -> Self {
+        let height = round_rocks.len();
+        let row_segments = cube_rocks
+            .iter()
+            .map(|cubes| segments(cubes, width))
+            .collect();
+        let col_segments = transpose(&cube_rocks, width)
+            .iter()
+            .map(|cubes| segments(cubes, height))
+            .collect();
+        let round_cols = transpose(&round_rocks, width);
+        Self {
+            round_rocks,
+            round_cols,
+            cube_rocks,
+            row_segments,
+            col_segments,
+            width,
+            height,
+        }
+    }
+    fn get(&self, x: usize, y: usize) -> char {
+        if self.round_rocks[y].get(x) {
+            'O'
+        } else if self.cube_rocks[y].get(x) {
+            '#'
+        } else {
+            '.'
+        }
+    }
+    fn total_load(&self) -> usize {
+        self.load(Dir::North)
+    }
+    fn load(&self, edge: Dir) -> usize {
         self.round_rocks
             .iter()
-            .map(|coord| self.size - coord.y)
+            .enumerate()
+            .flat_map(|(y, row)| row.ones().map(move |x| (x, y)))
+            .map(|(x, y)| match edge {
+                Dir::North => self.height - y,
+                Dir::South => y + 1,
+                Dir::West => self.width - x,
+                Dir::East => x + 1,
+            })
             .sum()
     }
+    fn loads(&self) -> [usize; 4] {
+        SPIN.map(|edge| self.load(edge))
+    }
     fn tilt(mut self, dir: Dir) -> Self {
-        let mut rolled_rocks = HashSet::new();
-        let mut roll = |x, y, next_pos: &mut IVec2| {
-            if self.cube_rocks.contains(&IVec2::new(x, y)) {
-                *next_pos = IVec2::new(x, y) + dir.0;
+        match dir {
+            Dir::West | Dir::East => roll(
+                &mut self.round_rocks,
+                &mut self.round_cols,
+                &self.row_segments,
+                dir == Dir::West,
+            ),
+            Dir::North | Dir::South => roll(
+                &mut self.round_cols,
+                &mut self.round_rocks,
+                &self.col_segments,
+                dir == Dir::North,
+            ),
+        }
+        self
+    }
+    fn tilt_all(self, dirs: &[Dir]) -> Self {
+        dirs.iter().fold(self, |platform, &dir| platform.tilt(dir))
+    }
+    fn state(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.round_rocks.hash(&mut hasher);
+        hasher.finish()
+    }
+    fn find_period(
+        self,
+        dirs: &[Dir],
+        times: usize,
+        mut visit: impl FnMut(&Self),
+    ) -> (Self, Option<(usize, usize)>) {
+        let mut seen = HashMap::<u64, Vec<usize>>::new();
+        let mut platform = self;
+        for done in 0..=times {
+            let candidates = seen.entry(platform.state()).or_default();
+            // a hash hit is only a candidate: spin one more period and check the
+            // platform comes back to the state it was hit with
+            let start = candidates.iter().copied().find(|&start| {
+                let spun = (start..done).fold(platform.clone(), |p, _| p.tilt_all(dirs));
+                spun.round_rocks == platform.round_rocks
+            });
+            if let Some(start) = start {
+                return (platform, Some((start, done)));
             }
-            if self.round_rocks.contains(&IVec2::new(x, y)) {
-                rolled_rocks.insert(*next_pos);
-                *next_pos += dir.0
+            candidates.push(done);
+            visit(&platform);
+            if done < times {
+                platform = platform.tilt_all(dirs);
             }
-        };
-
-        for x in 0..self.size {
-            match dir {
-                NORTH => {
-                    let mut next_pos = IVec2::new(x, 0);
-                    for y in 0..self.size {
-                        roll(x, y, &mut next_pos)
-                    }
-                }
-                SOUTH => {
-                    let mut next_pos = IVec2::new(x, self.size - 1);
-                    for y in (0..self.size).rev() {
-                        roll(x, y, &mut next_pos)
-                    }
-                }
-
-                WEST => {
-                    let mut next_pos = IVec2::new(0, x);
-                    for y in 0..self.size {
-                        roll(y, x, &mut next_pos)
-                    }
-                }
-                EAST => {
-                    let mut next_pos = IVec2::new(self.size - 1, x);
-                    for y in (0..self.size).rev() {
-                        roll(y, x, &mut next_pos)
-                    }
-                }
-                _ => {
-                    unimplemented!("no other direction yet")
-                }
-            };
         }
-        self.round_rocks = rolled_rocks;
-        self
+        (platform, None)
     }
-    fn cycle(self) -> Self {
-        self.tilt(NORTH).tilt(WEST).tilt(SOUTH).tilt(EAST)
-    }
-    fn spin_1_000_000_000(mut self) -> Self {
-        let mut vec_of_round_rocks = vec![self.round_rocks.clone()];
-        let mut cycles = 0;
-        let repeat_cycle = loop {
-            self = self.cycle();
-            cycles += 1;
-            let next = self.round_rocks.clone();
-            let cycle_pos = vec_of_round_rocks.iter().position(|old| old == &next);
-            if let Some(pos) = cycle_pos {
-                break pos;
+    fn repeat(self, dirs: &[Dir], times: usize) -> Self {
+        let (mut platform, period) = self.find_period(dirs, times, |_| {});
+        if let Some((start, done)) = period {
+            for _ in 0..(times - done) % (done - start) {
+                platform = platform.tilt_all(dirs);
             }
-            vec_of_round_rocks.push(next);
-        };
-        for _ in 0..(1_000_000_000 - cycles) % (cycles - repeat_cycle) {
-            self = self.cycle()
         }
-        self
+        platform
+    }
+    fn series(self, dirs: &[Dir]) -> LoadSeries {
+        let mut loads = vec![];
+        let (_, period) =
+            self.find_period(dirs, usize::MAX, |platform| loads.push(platform.loads()));
+        let (start, _) = period.expect("a platform has finitely many states");
+        LoadSeries { loads, start }
+    }
+    fn run(self, program: &Program) -> Self {
+        program.blocks.iter().fold(self, |platform, (dirs, times)| {
+            platform.repeat(dirs, *times)
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct LoadSeries {
+    loads: Vec<[usize; 4]>,
+    start: usize,
+}
+impl LoadSeries {
+    fn index(&self, cycles: usize) -> usize {
+        if cycles < self.loads.len() {
+            cycles
+        } else {
+            self.start + (cycles - self.start) % self.period()
+        }
+    }
+    fn period(&self) -> usize {
+        self.loads.len() - self.start
+    }
+    fn at(&self, cycles: usize, edge: Dir) -> usize {
+        self.loads[self.index(cycles)][edge as usize]
+    }
+    fn prefix(&self, edge: Dir) -> Vec<usize> {
+        self.loads[..self.start]
+            .iter()
+            .map(|loads| loads[edge as usize])
+            .collect()
+    }
+    fn periodic(&self, edge: Dir) -> Vec<usize> {
+        self.loads[self.start..]
+            .iter()
+            .map(|loads| loads[edge as usize])
+            .collect()
     }
 }
 
 impl Display for Platform {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for y in 0..self.size {
-            for x in 0..self.size {
-                if self.round_rocks.contains(&IVec2::new(x, y)) {
-                    write!(f, "O")?;
-                } else if self.cube_rocks.contains(&IVec2::new(x, y)) {
-                    write!(f, "#")?;
-                } else {
-                    write!(f, ".")?;
-                }
+        for y in 0..self.height {
+            for x in 0..self.width {
+                write!(f, "{}", self.get(x, y))?;
             }
             writeln!(f)?;
         }
@@ -117,34 +346,43 @@ impl Display for Platform {
 }
 
 fn parse(input: &str) -> Platform {
-    let lines = input.lines();
-    let mut platform = Platform::default();
-    platform.size = lines
-        .enumerate()
-        .map(|(line_i, line)| {
-            line.chars()
-                .enumerate()
-                .for_each(|(char_i, char)| match char {
-                    '#' => {
-                        platform
-                            .cube_rocks
-                            .insert(IVec2::new(char_i as i32, line_i as i32));
-                    }
-                    'O' => {
-                        platform
-                            .round_rocks
-                            .insert(IVec2::new(char_i as i32, line_i as i32));
-                    }
+    let width = input.lines().map(str::len).max().unwrap_or(0);
+    let (round_rocks, cube_rocks) = input
+        .lines()
+        .map(|line| {
+            let (mut round, mut cube) = (BitSet::new(width), BitSet::new(width));
+            for (x, char) in line.chars().enumerate() {
+                match char {
+                    '#' => cube.set(x),
+                    'O' => round.set(x),
                     _ => {}
-                })
+                }
+            }
+            (round, cube)
         })
-        .count() as i32;
-    platform
+        .unzip();
+    Platform::new(round_rocks, cube_rocks, width)
 }
 fn main() {
     let input = read_to_string("inputs/day14-input1.txt").unwrap();
     let platform = parse(&input);
-    let answer = part2(platform);
+    if std::env::args().any(|arg| arg == "--series") {
+        let series = platform.clone().series(&SPIN);
+        println!(
+            "north loads before the loop: {:?}",
+            series.prefix(Dir::North)
+        );
+        println!(
+            "north loads over a period of {}: {:?}",
+            series.period(),
+            series.periodic(Dir::North)
+        );
+        println!(
+            "north load after 1000000000 spins: {}",
+            series.at(1_000_000_000, Dir::North)
+        );
+    }
+    let answer = part2(platform).unwrap();
     println!("answer is: {answer}");
 }
 #[cfg(test)]
@@ -197,25 +435,139 @@ O..#.OO...
     #[test]
     fn tilt_test() {
         let platform = parse(INPUT.trim());
-        let tilted = platform.tilt(NORTH);
+        let tilted = platform.tilt(Dir::North);
         assert_eq!(format!("{}", tilted).trim(), TILTED.trim());
     }
     #[test]
     fn cycle_1() {
         let platform = parse(INPUT.trim());
-        let cycle_1 = platform.cycle();
+        let cycle_1 = platform.tilt_all(&SPIN);
 
         assert_eq!(format!("{}", cycle_1).trim(), CYCLE_1.trim());
     }
     #[test]
     fn part2_test() {
         let platform = parse(INPUT.trim());
-        assert_eq!(part2(platform), 64);
+        assert_eq!(part2(platform).unwrap(), 64);
     }
     #[test]
     fn total_load() {
         let platform = parse(INPUT.trim());
-        let tilted = platform.tilt(NORTH);
+        let tilted = platform.tilt(Dir::North);
         assert_eq!(tilted.total_load(), 136);
     }
+    #[test]
+    fn programs() -> anyhow::Result<()> {
+        let program = parse_program("N W S E x3 N")?;
+        assert_eq!(
+            program.blocks,
+            vec![
+                (vec![Dir::North, Dir::West, Dir::South, Dir::East], 3),
+                (vec![Dir::North], 1)
+            ]
+        );
+        assert!(parse_program("x3").is_err());
+        assert!(parse_program("N Q").is_err());
+        assert!(parse_program("N xlots").is_err());
+
+        let platform = parse("O.#\n...\n.O.\n..O\n#..");
+        assert_eq!((platform.width, platform.height), (3, 5));
+        let tilted = platform.clone().run(&parse_program("S E")?);
+        assert_eq!(tilted.to_string(), "..#\n...\n...\n..O\n#OO\n");
+        let spun = platform.clone().run(&parse_program("N W S E x1000000001")?);
+        let stepped = platform.run(&parse_program("N W S E x1000000000 N W S E")?);
+        assert_eq!(spun, stepped);
+        Ok(())
+    }
+    fn generated(width: usize, height: usize) -> String {
+        let mut state = 12345_u64;
+        (0..height)
+            .map(|_| {
+                (0..width)
+                    .map(|_| {
+                        state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+                        match state >> 59 {
+                            0..=3 => '#',
+                            4..=12 => 'O',
+                            _ => '.',
+                        }
+                    })
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+    fn naive_north(grid: &mut [Vec<char>]) {
+        for x in 0..grid[0].len() {
+            let mut free = 0;
+            for y in 0..grid.len() {
+                match grid[y][x] {
+                    '#' => free = y + 1,
+                    'O' => {
+                        grid[y][x] = '.';
+                        grid[free][x] = 'O';
+                        free += 1;
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+    fn rotate_clockwise(grid: &[Vec<char>]) -> Vec<Vec<char>> {
+        (0..grid[0].len())
+            .map(|x| (0..grid.len()).rev().map(|y| grid[y][x]).collect())
+            .collect()
+    }
+    #[test]
+    fn bitboard_matches_naive() {
+        let input = generated(150, 70);
+        let mut grid = input
+            .lines()
+            .map(|line| line.chars().collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let mut platform = parse(&input);
+        for _ in 0..3 {
+            for _ in 0..4 {
+                naive_north(&mut grid);
+                grid = rotate_clockwise(&grid);
+            }
+            platform = platform.tilt_all(&SPIN);
+        }
+        let expected = grid
+            .iter()
+            .map(|row| row.iter().collect::<String>() + "\n")
+            .collect::<String>();
+        assert_eq!(platform.to_string(), expected);
+    }
+    #[test]
+    fn large_spin() -> anyhow::Result<()> {
+        let platform = parse(&generated(200, 120));
+        let program = parse_program("N W S E x1000000000")?;
+        let spun = platform.clone().run(&program);
+        let period = parse_program("N W S E x999999999")?;
+        assert_eq!(platform.run(&period).tilt_all(&SPIN), spun);
+        Ok(())
+    }
+    #[test]
+    fn load_series() -> anyhow::Result<()> {
+        let platform = parse(INPUT.trim());
+        let series = platform.clone().series(&SPIN);
+        assert_eq!(series.period(), 7);
+        assert_eq!(series.prefix(Dir::North).len(), series.start);
+        assert_eq!(series.periodic(Dir::North).len(), 7);
+        assert_eq!(series.at(1_000_000_000, Dir::North), 64);
+        for cycles in [0, 1, 2, 3, 10, 11, 12, 25] {
+            let program = parse_program(&format!("N W S E x{cycles}"))?;
+            let spun = platform.clone().run(&program);
+            for edge in [Dir::North, Dir::West, Dir::South, Dir::East] {
+                assert_eq!(series.at(cycles, edge), spun.load(edge));
+            }
+        }
+        let tilted = platform.clone().series(&[Dir::North]);
+        assert_eq!(tilted.at(1, Dir::North), 136);
+        assert_eq!(tilted.at(5, Dir::North), 136);
+        assert_eq!(platform.load(Dir::North), 104);
+        assert_eq!(platform.load(Dir::South) + 104, 11 * 18);
+        Ok(())
+    }
 }
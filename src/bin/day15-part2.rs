@@ -8,9 +8,7 @@ fn hash(input: &str) -> usize {
         .chars()
         .fold(0, |acc, ch| (acc + ch as usize) * 17 % 256)
 }
-const EMPTY: Vec<(&str, usize)> = Vec::new();
-
-fn focusing_power(boxes: &[Vec<(&str, usize)>]) -> usize {
+fn focusing_power<K>(boxes: &[Vec<(K, usize)>]) -> usize {
     boxes
         .iter()
         .enumerate()
@@ -22,34 +20,148 @@ fn focusing_power(boxes: &[Vec<(&str, usize)>]) -> usize {
         .sum()
 }
 
-fn hashmap<'a>(sequence: &'a [&'a str]) -> [Vec<(&'a str, usize)>; 256] {
-    sequence.iter().fold([EMPTY; 256], |mut acc, &step| {
-        let (label, focal_len) = step.split_once(['-', '=']).expect("step parsed");
-        let box_i = hash(label);
-        let slot_i = acc[box_i].iter().position(|(l, _)| *l == label);
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Step<K> {
+    Insert(K, usize),
+    Remove(K),
+}
+impl<'a> Step<&'a str> {
+    fn parse(step: &'a str) -> anyhow::Result<Self> {
+        let (label, focal_len) = step
+            .split_once(['-', '='])
+            .ok_or_else(|| anyhow::anyhow!("step {step:?} has no operation"))?;
+        if step.as_bytes()[label.len()] == b'-' {
+            if !focal_len.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "unexpected {focal_len:?} after '-' in {step:?}"
+                ));
+            }
+            Ok(Step::Remove(label))
+        } else {
+            Ok(Step::Insert(label, focal_len.parse()?))
+        }
+    }
+}
 
-        if !focal_len.is_empty() {
-            let focal_len = focal_len.parse().expect("focal lenght parsed");
-            if let Some(slot_i) = slot_i {
-                acc[box_i].push((label, focal_len));
-                acc[box_i].swap_remove(slot_i);
-            } else {
-                acc[box_i].push((label, focal_len));
+#[derive(Debug, Clone)]
+struct LensBoxes<K> {
+    boxes: Vec<Vec<(K, usize)>>,
+    hasher: fn(&str) -> usize,
+    log: Vec<Step<K>>,
+}
+impl<K: AsRef<str> + Clone> LensBoxes<K> {
+    fn new() -> Self {
+        Self::with_hasher(hash)
+    }
+    fn with_hasher(hasher: fn(&str) -> usize) -> Self {
+        Self {
+            boxes: vec![Vec::new(); 256],
+            hasher,
+            log: vec![],
+        }
+    }
+    fn bucket(&self, label: &str) -> usize {
+        (self.hasher)(label) % self.boxes.len()
+    }
+    fn slot(&self, label: &str) -> (usize, Option<usize>) {
+        let box_i = self.bucket(label);
+        let slot_i = self.boxes[box_i]
+            .iter()
+            .position(|(l, _)| l.as_ref() == label);
+        (box_i, slot_i)
+    }
+    fn insert(&mut self, label: K, focal_len: usize) -> Option<usize> {
+        self.log.push(Step::Insert(label.clone(), focal_len));
+        match self.slot(label.as_ref()) {
+            (box_i, Some(slot_i)) => {
+                let previous = self.boxes[box_i][slot_i].1;
+                self.boxes[box_i][slot_i].1 = focal_len;
+                Some(previous)
+            }
+            (box_i, None) => {
+                self.boxes[box_i].push((label, focal_len));
+                None
             }
-        } else if let Some(slot_i) = slot_i {
-            acc[box_i].remove(slot_i);
         }
-        acc
-    })
+    }
+    fn remove(&mut self, label: K) -> Option<usize> {
+        let (box_i, slot_i) = self.slot(label.as_ref());
+        self.log.push(Step::Remove(label));
+        slot_i.map(|slot_i| self.boxes[box_i].remove(slot_i).1)
+    }
+    fn get(&self, label: &str) -> Option<usize> {
+        let (box_i, slot_i) = self.slot(label);
+        slot_i.map(|slot_i| self.boxes[box_i][slot_i].1)
+    }
+    fn iter(&self) -> impl Iterator<Item = (usize, usize, &K, usize)> {
+        self.boxes.iter().enumerate().flat_map(|(box_i, boxx)| {
+            boxx.iter()
+                .enumerate()
+                .map(move |(slot_i, (label, focal_len))| (box_i, slot_i, label, *focal_len))
+        })
+    }
+    fn apply(&mut self, step: Step<K>) -> Option<usize> {
+        match step {
+            Step::Insert(label, focal_len) => self.insert(label, focal_len),
+            Step::Remove(label) => self.remove(label),
+        }
+    }
+    fn log(&self) -> &[Step<K>] {
+        &self.log
+    }
+    fn replay(&self, steps: usize) -> Self {
+        let mut replayed = Self::with_hasher(self.hasher);
+        for step in self.log.iter().take(steps) {
+            replayed.apply(step.clone());
+        }
+        replayed
+    }
+    fn to_owned_keys(&self) -> LensBoxes<String> {
+        let mut owned = LensBoxes::with_hasher(self.hasher);
+        for step in &self.log {
+            owned.apply(match step {
+                Step::Insert(label, focal_len) => {
+                    Step::Insert(label.as_ref().to_owned(), *focal_len)
+                }
+                Step::Remove(label) => Step::Remove(label.as_ref().to_owned()),
+            });
+        }
+        owned
+    }
+    fn focusing_power(&self) -> usize {
+        focusing_power(&self.boxes)
+    }
 }
-fn part2(sequence: &[&str]) -> usize {
-    focusing_power(&hashmap(sequence))
+
+fn hashmap<'a>(sequence: &[&'a str]) -> anyhow::Result<LensBoxes<&'a str>> {
+    let mut boxes = LensBoxes::new();
+    for step in sequence {
+        boxes.apply(Step::parse(step)?);
+    }
+    Ok(boxes)
+}
+fn part2(sequence: &[&str]) -> anyhow::Result<usize> {
+    Ok(hashmap(sequence)?.focusing_power())
 }
 
 fn main() {
     let input = read_to_string("inputs/day15-input1.txt").unwrap();
     let sequence = parse(input.trim());
-    let answer = part2(&sequence);
+    if std::env::args().any(|arg| arg == "--log") {
+        let boxes = hashmap(&sequence).unwrap().to_owned_keys();
+        let steps = boxes.log().len();
+        for done in [steps / 4, steps / 2, 3 * steps / 4, steps] {
+            let power = boxes.replay(done).focusing_power();
+            println!("focusing power after {done} of {steps} steps: {power}");
+        }
+        for (box_i, slot_i, label, focal_len) in boxes.iter() {
+            println!("box {box_i} slot {slot_i}: {label} {focal_len}");
+        }
+        if let Some(Step::Insert(label, _) | Step::Remove(label)) = boxes.log().last() {
+            println!("last label {label} holds {:?}", boxes.get(label));
+        }
+    }
+    let answer = part2(&sequence).unwrap();
 
     println!("answer is: {answer}");
 }
@@ -60,6 +172,8 @@ mod tests {
 
     use super::*;
 
+    const EMPTY: Vec<(&str, usize)> = Vec::new();
+
     const INPUT: &str = "rn=1,cm-,qp=3,cm=2,qp-,pc=4,ot=9,ab=5,pc-,pc=6,ot=7";
     #[test]
     fn parsing() {
@@ -95,13 +209,13 @@ mod tests {
         let mut boxes = [EMPTY; 256];
         boxes[0] = vec![("rn", 1), ("cm", 2)];
         boxes[3] = vec![("ot", 7), ("ab", 5), ("pc", 6)];
-        assert_eq!(hashmap(&sequence)[..4], boxes[..4]);
+        assert_eq!(hashmap(&sequence).unwrap().boxes[..4], boxes[..4]);
     }
     #[test]
     fn label() {
         let str = "ab-";
         assert_eq!(str.split_once(['-', '=']), Some(("ab", "")));
-        assert!("".is_empty());
+        assert!(Step::parse(str).is_ok_and(|step| step == Step::Remove("ab")));
 
         let str = "ab=9";
         assert_eq!(str.split_once(['-', '=']), Some(("ab", "9")));
@@ -109,6 +223,39 @@ mod tests {
     #[test]
     fn part2_test() {
         let sequence = parse(INPUT.trim());
-        assert_eq!(part2(&sequence), 145);
+        assert_eq!(part2(&sequence).unwrap(), 145);
+    }
+    #[test]
+    fn lens_boxes() -> anyhow::Result<()> {
+        let sequence = parse(INPUT.trim());
+        let boxes = hashmap(&sequence)?;
+        assert_eq!(boxes.get("pc"), Some(6));
+        assert_eq!(boxes.get("qp"), None);
+        assert_eq!(boxes.log().len(), sequence.len());
+        let labels = boxes
+            .iter()
+            .map(|(_, _, label, _)| *label)
+            .collect::<Vec<_>>();
+        assert_eq!(labels, vec!["rn", "cm", "ot", "ab", "pc"]);
+
+        let early = boxes.replay(3);
+        assert_eq!(early.get("qp"), Some(3));
+        assert_eq!(early.get("cm"), None);
+        assert_eq!(boxes.replay(sequence.len()).focusing_power(), 145);
+
+        let mut owned = boxes.to_owned_keys();
+        assert_eq!(owned.insert(String::from("ot"), 1), Some(7));
+        assert_eq!(owned.remove(String::from("rn")), Some(1));
+        assert_eq!(owned.get("cm"), Some(2));
+
+        let mut single = LensBoxes::with_hasher(|_| 0);
+        single.insert("a", 1);
+        single.insert("b", 2);
+        single.insert("a", 3);
+        assert_eq!(single.boxes[0], vec![("a", 3), ("b", 2)]);
+        assert!(Step::parse("ab").is_err());
+        assert!(Step::parse("ab=x").is_err());
+        assert!(Step::parse("ab-5").is_err());
+        Ok(())
     }
 }